@@ -0,0 +1,43 @@
+use crate::Sudoku;
+use crate::relabel::value_rules;
+
+/// Whether some rule of the sudoku tells the two digits apart even though they are never given
+fn distinguished(sudoku: &Sudoku, a: u64, b: u64) -> bool {
+    let one_of = |digits: &[u64]| digits.contains(&a) != digits.contains(&b);
+    sudoku.digit_regions.iter().any(|region| one_of(&region.digits))
+        || sudoku.quadruples.iter().any(|quadruple| one_of(&quadruple.digits))
+        || sudoku.digit_counts.iter().any(|count| count.digit == a || count.digit == b)
+        || sudoku.state.pencilmarks.iter().any(|mark| one_of(&mark.digits))
+        || sudoku.state.filled.iter().flatten().flatten().any(|&digit| digit == a || digit == b)
+        || !value_rules(sudoku).is_empty()
+}
+
+/// How often each digit is given, and whether the missing ones rule out a unique solution. Two digits that are
+/// never given and that no rule tells apart can be swapped in any solution to give another one.
+pub fn given_distribution(sudoku: &Sudoku) -> Vec<String> {
+    let mut lines = vec!["Given digits:".to_string()];
+    let givens = sudoku.given.iter().flatten().flatten().copied().collect::<Vec<_>>();
+    for &digit in &sudoku.digits {
+        let count = givens.iter().filter(|&&given| given == digit).count();
+        lines.push(format!("  {digit}: {count}"));
+    }
+    lines.push(format!("{} givens in total.", givens.len()));
+    let missing = sudoku.digits.iter().copied().filter(|digit| !givens.contains(digit)).collect::<Vec<_>>();
+    match missing[..] {
+        [] => lines.push("Every digit is given at least once.".to_string()),
+        [digit] => lines.push(format!("Digit {digit} is not given, which is fine as long as no other digit is missing.")),
+        _ => {
+            let names = missing.iter().map(|digit| digit.to_string()).collect::<Vec<_>>().join(", ");
+            lines.push(format!("Digits {names} are not given."));
+            let swappable = missing.iter().enumerate().flat_map(|(k, &a)| missing[k + 1..].iter().map(move |&b| (a, b)))
+                .find(|&(a, b)| !distinguished(sudoku, a, b));
+            match swappable {
+                Some((a, b)) => lines.push(format!(
+                    "Warning: swapping {a} and {b} in any solution gives another, so the puzzle cannot have a unique solution. At most one digit can be missing from the givens."
+                )),
+                None => lines.push("Other rules tell the missing digits apart, so they may still be forced.".to_string()),
+            }
+        }
+    }
+    lines
+}
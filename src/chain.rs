@@ -0,0 +1,63 @@
+use crate::logic::{Candidates, Step, Technique};
+use crate::session::Session;
+
+/// Assuming a digit in a square and following the techniques from there until a square runs out of candidates
+pub struct Chain {
+    pub cell: (usize, usize),
+    pub digit: u64,
+    pub steps: Vec<Step>,
+    pub dead_end: String,
+}
+
+impl Chain {
+    /// The elimination the chain proves
+    pub fn step(&self) -> Step {
+        let (i, j) = self.cell;
+        Step {
+            technique: Technique::Chain,
+            digits: vec![self.digit],
+            cells: vec![self.cell],
+            base: Vec::new(),
+            cover: Vec::new(),
+            placement: None,
+            eliminations: vec![(i, j, self.digit)],
+        }
+    }
+
+    pub fn explain(&self) -> Vec<String> {
+        let (i, j) = self.cell;
+        let mut lines = vec![format!("Suppose r{i}c{j} were {}.", self.digit)];
+        lines.extend(self.steps.iter().map(|step| format!("Then {}", step.explain())));
+        lines.push(format!("Then {}, so r{i}c{j} cannot be {}.", self.dead_end, self.digit));
+        lines
+    }
+}
+
+/// Finds the shortest chain disproving a candidate, trying the candidates that differ from a solution found by the solver
+pub fn find_chain(candidates: &Candidates, session: &Session) -> Option<Chain> {
+    let solution = session.solve()?;
+
+    let mut options = Vec::new();
+    for (i, row) in candidates.grid.iter().enumerate() {
+        for (j, digits) in row.iter().enumerate() {
+            if digits.len() > 1 {
+                options.extend(digits.iter().filter(|&&digit| digit != solution[i][j]).map(|&digit| (digits.len(), i, j, digit)));
+            }
+        }
+    }
+    options.sort();
+
+    let mut best: Option<Chain> = None;
+    for (_, i, j, digit) in options {
+        let mut assumed = candidates.clone();
+        assumed.apply(&assumed.assume(i, j, digit));
+        let steps = assumed.solve_up_to(best.as_ref().map_or(usize::MAX, |best| best.steps.len()));
+        let Some(dead_end) = assumed.dead_end() else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|best| steps.len() < best.steps.len()) {
+            best = Some(Chain { cell: (i, j), digit, steps, dead_end });
+        }
+    }
+    best
+}
@@ -0,0 +1,119 @@
+use z3::{Context, SatResult, Solver};
+use z3::ast::{Ast, Bool, Int};
+
+use crate::normalize::sudoku_to_json;
+use crate::solver::add_solver_constraints;
+use crate::{cage_clue_ast, new_grid, CageRelation, Clue, Frame, Sandwich, Side, Skyscraper, Sudoku, XSum};
+
+fn sandwich_sum(line: &[u64], crusts: [u64; 2]) -> Option<u64> {
+    let first = line.iter().position(|&d| d == crusts[0])?;
+    let second = line.iter().position(|&d| d == crusts[1])?;
+    Some(line[first.min(second) + 1..first.max(second)].iter().sum())
+}
+
+fn x_sum(line: &[u64]) -> u64 {
+    line.iter().take(line[0] as usize).sum()
+}
+
+fn skyscraper(line: &[u64]) -> u64 {
+    let mut tallest = 0;
+    let mut visible = 0;
+    for &d in line {
+        if d > tallest {
+            tallest = d;
+            visible += 1;
+        }
+    }
+    visible
+}
+
+pub fn fill_border_clues(sudoku: &Sudoku, solution: &[Vec<u64>], borders: &[(Side, usize)], output: Option<String>) {
+    let mut filled = sudoku.clone();
+    for &(side, index) in borders {
+        let line = side.line(index, solution.len()).iter().map(|&(i, j)| solution[i][j]).collect::<Vec<_>>();
+        let sum = sandwich_sum(&line, sudoku.crusts());
+        let frame = side.frame(index, solution.len()).iter().map(|&(i, j)| solution[i][j]).sum();
+        match sum {
+            Some(sum) => println!("{} {index}: sandwich {sum}, X-sum {}, skyscraper {}, frame {frame}", side.name(), x_sum(&line), skyscraper(&line)),
+            None => println!("{} {index}: no sandwich, X-sum {}, skyscraper {}, frame {frame}", side.name(), x_sum(&line), skyscraper(&line)),
+        }
+        filled.frame.retain(|frame| (frame.side, frame.index) != (side, index));
+        filled.frame.push(Frame { side, index, sum: Clue::Known(frame) });
+        filled.skyscraper.retain(|skyscraper| (skyscraper.side, skyscraper.index) != (side, index));
+        filled.skyscraper.push(Skyscraper { side, index, count: Clue::Known(skyscraper(&line)) });
+        filled.x_sum.retain(|x_sum| (x_sum.side, x_sum.index) != (side, index));
+        filled.x_sum.push(XSum { side, index, sum: Clue::Known(x_sum(&line)) });
+        if let Some(sum) = sum {
+            filled.sandwich.retain(|sandwich| (sandwich.side, sandwich.index) != (side, index));
+            filled.sandwich.push(Sandwich { side, index, sum: Clue::Known(sum), crusts: sudoku.crusts() });
+        }
+    }
+    if let Some(path) = output {
+        std::fs::write(&path, serde_json::to_string_pretty(&sudoku_to_json(&filled)).unwrap() + "\n").unwrap();
+        println!("Sandwich, X-sum, skyscraper and frame clues written to {path}");
+    }
+}
+
+/// The clue of every killer cage for the solution, which for mystery killers is the sum shifted by a written
+/// offset and reduced by the modulus. An unknown offset is left at 0.
+fn cage_totals(sudoku: &Sudoku, solution: &[Vec<u64>]) -> Vec<u64> {
+    let offset = match sudoku.cage_sum_offset {
+        Some(Clue::Known(offset)) => offset,
+        _ => 0,
+    };
+    sudoku.killer_cages.iter().map(|cage| {
+        let total = cage.cells.iter().map(|cell| solution[cell[0]][cell[1]]).sum::<u64>() + offset;
+        sudoku.cage_sum_modulo.map_or(total, |modulo| total % modulo)
+    }).collect()
+}
+
+/// Indices of the killer cages whose sums are enough to pin down the solution, found by dropping each sum in
+/// turn while the others still do. None when the solution is not unique even with every sum.
+pub fn needed_cage_sums(sudoku: &Sudoku, solution: &[Vec<u64>], ctx: &Context) -> Option<Vec<usize>> {
+    let mut unsummed = sudoku.clone();
+    for cage in &mut unsummed.killer_cages {
+        cage.sum = None;
+    }
+    let grid = new_grid(ctx, sudoku.size());
+    let solver = Solver::new(ctx);
+    add_solver_constraints(&unsummed, &grid, &solver, ctx);
+    let differs = grid.iter().zip(solution).flat_map(|(squares, row)| squares.iter().zip(row).map(
+        |(square, &digit)| square._eq(&Int::from_u64(ctx, digit)).not()
+    )).collect::<Vec<_>>();
+    solver.assert(&Bool::or(ctx, &differs.iter().collect::<Vec<_>>()));
+    let keep = (0..sudoku.killer_cages.len()).map(|i| Bool::new_const(ctx, format!("keepCage{i}"))).collect::<Vec<_>>();
+    for (i, total) in cage_totals(sudoku, solution).into_iter().enumerate() {
+        solver.assert(&keep[i].implies(&cage_clue_ast(&unsummed, i, ctx)._eq(&Int::from_u64(ctx, total))));
+    }
+    if solver.check_assumptions(&keep) != SatResult::Unsat {
+        return None;
+    }
+    let mut needed = (0..keep.len()).collect::<Vec<_>>();
+    for i in 0..keep.len() {
+        let others = needed.iter().filter(|&&k| k != i).map(|&k| keep[k].clone()).collect::<Vec<_>>();
+        if solver.check_assumptions(&others) == SatResult::Unsat {
+            needed.retain(|&k| k != i);
+        }
+    }
+    Some(needed)
+}
+
+/// Writes the sum of every killer cage from the solution into the puzzle, or only the sums listed in `needed`
+pub fn fill_cage_sums(sudoku: &Sudoku, solution: &[Vec<u64>], needed: Option<&[usize]>, output: Option<String>) {
+    let mut filled = sudoku.clone();
+    for (i, (cage, total)) in filled.killer_cages.iter_mut().zip(cage_totals(sudoku, solution)).enumerate() {
+        let first = &cage.cells[0];
+        if needed.is_none_or(|needed| needed.contains(&i)) {
+            println!("Cage {i} at r{}c{}: sum {total}", first[0], first[1]);
+            cage.sum = Some(Clue::Known(total));
+            cage.relation = CageRelation::Equal;
+        } else {
+            println!("Cage {i} at r{}c{}: sum {total}, not needed", first[0], first[1]);
+            cage.sum = None;
+        }
+    }
+    if let Some(path) = output {
+        std::fs::write(&path, serde_json::to_string_pretty(&sudoku_to_json(&filled)).unwrap() + "\n").unwrap();
+        println!("Cage sums written to {path}");
+    }
+}
@@ -0,0 +1,140 @@
+use clap::{Arg, Command};
+
+use crate::Shell;
+
+/// Help text made safe to put inside quotes in a completion script
+fn help(text: Option<&clap::builder::StyledStr>) -> String {
+    let help = text.map(|help| help.to_string()).unwrap_or_default();
+    help.chars().filter(|c| !"'\"[]:`$\\".contains(*c)).collect()
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values().iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_string()).collect()
+}
+
+fn flags(arg: &Arg) -> Vec<String> {
+    arg.get_short().map(|short| format!("-{short}")).into_iter().chain(arg.get_long().map(|long| format!("--{long}"))).collect()
+}
+
+/// The flags a mode accepts, its own followed by the global ones once the command is built
+fn mode_arguments(mode: &Command) -> Vec<&Arg> {
+    mode.get_arguments().filter(|arg| arg.get_long().is_some() || arg.get_short().is_some()).collect()
+}
+
+/// The name and aliases of a mode, as alternatives for a shell pattern
+fn mode_names(mode: &Command) -> Vec<&str> {
+    std::iter::once(mode.get_name()).chain(mode.get_all_aliases()).collect()
+}
+
+fn bash(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut values_cases = String::new();
+    let all = command.get_subcommands().flat_map(|mode| mode_arguments(mode)).filter(|arg| takes_value(arg)).collect::<Vec<_>>();
+    let mut seen = Vec::new();
+    for arg in all {
+        let patterns = flags(arg).into_iter().filter(|flag| !seen.contains(flag)).collect::<Vec<_>>();
+        if patterns.is_empty() {
+            continue;
+        }
+        seen.extend(patterns.clone());
+        let values = values(arg);
+        let reply = if values.is_empty() { "compgen -f -- \"$cur\"".to_string() } else { format!("compgen -W \"{}\" -- \"$cur\"", values.join(" ")) };
+        values_cases += &format!("        {})\n            COMPREPLY=($({reply}))\n            return ;;\n", patterns.join("|"));
+    }
+    let mut mode_cases = String::new();
+    for mode in command.get_subcommands() {
+        let words = mode_arguments(mode).iter().flat_map(|arg| flags(arg)).collect::<Vec<_>>().join(" ");
+        mode_cases += &format!("            {})\n                COMPREPLY=($(compgen -W \"{words}\" -- \"$cur\"))\n                return ;;\n", mode_names(mode).join("|"));
+    }
+    let modes = command.get_subcommands().map(|mode| mode.get_name()).collect::<Vec<_>>().join(" ");
+    format!(
+        "{function}() {{\n    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" word\n    case \"$prev\" in\n{values_cases}    esac\n    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do\n        case \"$word\" in\n{mode_cases}        esac\n    done\n    COMPREPLY=($(compgen -W \"{modes}\" -- \"$cur\"))\n}}\ncomplete -F {function} {name}\n"
+    )
+}
+
+fn zsh_specs(args: &[&Arg]) -> Vec<String> {
+    let mut specs = Vec::new();
+    for arg in args {
+        let action = if !takes_value(arg) {
+            String::new()
+        } else if values(arg).is_empty() {
+            format!(":{}:_files", arg.get_id())
+        } else {
+            format!(":{}:({})", arg.get_id(), values(arg).join(" "))
+        };
+        for flag in flags(arg) {
+            specs.push(format!("'{flag}[{}]{action}'", help(arg.get_help())));
+        }
+    }
+    specs
+}
+
+fn zsh(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let modes = command.get_subcommands()
+        .map(|mode| format!("'{}:{}'", mode.get_name(), help(mode.get_about())))
+        .collect::<Vec<_>>().join("\n        ");
+    let mut cases = String::new();
+    for mode in command.get_subcommands() {
+        let positionals = mode.get_positionals()
+            .map(|arg| format!("'1:{}:({})'", arg.get_id(), values(arg).join(" ")))
+            .collect::<Vec<_>>();
+        let specs = zsh_specs(&mode_arguments(mode)).into_iter().chain(positionals).collect::<Vec<_>>();
+        cases += &format!("        {})\n            _arguments \\\n                {} ;;\n", mode_names(mode).join("|"), specs.join(" \\\n                "));
+    }
+    format!(
+        "#compdef {name}\n\n{function}() {{\n    local -a modes\n    modes=(\n        {modes}\n    )\n    if (( CURRENT == 2 )); then\n        _describe mode modes\n        return\n    fi\n    case $words[2] in\n{cases}    esac\n}}\n\n{function} \"$@\"\n"
+    )
+}
+
+fn fish(command: &Command) -> String {
+    let name = command.get_name();
+    let mut lines = Vec::new();
+    let flag = |arg: &Arg, condition: &str| {
+        let mut line = format!("complete -c {name}{condition}");
+        if let Some(short) = arg.get_short() {
+            line += &format!(" -s {short}");
+        }
+        if let Some(long) = arg.get_long() {
+            line += &format!(" -l {long}");
+        }
+        line += &format!(" -d '{}'", help(arg.get_help()));
+        if takes_value(arg) {
+            let values = values(arg);
+            line += &if values.is_empty() { " -r -F".to_string() } else { format!(" -x -a '{}'", values.join(" ")) };
+        }
+        line
+    };
+    for arg in command.get_arguments().filter(|arg| arg.is_global_set()) {
+        lines.push(flag(arg, ""));
+    }
+    for mode in command.get_subcommands() {
+        lines.push(format!("complete -c {name} -f -n __fish_use_subcommand -a {} -d '{}'", mode.get_name(), help(mode.get_about())));
+        let condition = format!(" -n '__fish_seen_subcommand_from {}'", mode_names(mode).join(" "));
+        for arg in mode_arguments(mode).into_iter().filter(|arg| !arg.is_global_set()) {
+            lines.push(flag(arg, &condition));
+        }
+        for arg in mode.get_positionals() {
+            lines.push(format!("complete -c {name}{condition} -f -a '{}'", values(arg).join(" ")));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// A completion script for the shell, with the modes, their flags and the values of the flags with choices
+/// taken from the clap definitions so it never falls behind them
+pub fn completions(command: &Command, shell: Shell) -> String {
+    let mut command = command.clone();
+    command.build();
+    match shell {
+        Shell::Bash => bash(&command),
+        Shell::Zsh => zsh(&command),
+        Shell::Fish => fish(&command),
+    }
+}
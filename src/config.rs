@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+const FILE_NAME: &str = "sudoku-z3.toml";
+
+/// The config files that exist, the user-wide one in the XDG config directory before the one in the current
+/// directory, so that the nearer file wins
+fn config_paths() -> Vec<PathBuf> {
+    let xdg = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let paths = [xdg.map(|dir| dir.join("sudoku-z3").join(FILE_NAME)), Some(PathBuf::from(FILE_NAME))];
+    paths.into_iter().flatten().filter(|path| path.is_file()).collect()
+}
+
+/// A string, number or bare word as it is passed on the command line
+fn parse_scalar(value: &str) -> Result<String, String> {
+    if let Some(text) = value.strip_prefix('"') {
+        let text = text.strip_suffix('"').ok_or_else(|| format!("unterminated string {value}"))?;
+        Ok(text.replace("\\\"", "\"").replace("\\\\", "\\"))
+    } else if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c)) {
+        Ok(value.to_string())
+    } else {
+        Err(format!("cannot read value {value}"))
+    }
+}
+
+/// The items of a one-line array, split at the commas outside quoted strings
+fn array_items(items: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (index, c) in items.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                split.push(&items[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&items[start..]);
+    split
+}
+
+/// The flags of one file, each with its value if it takes one. Only the flat part of TOML that flags need is
+/// read: `key = value` lines with strings, numbers, booleans and arrays of them, and `#` comments.
+fn parse_config(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut args = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |reason: String| format!("line {}: {reason}", number + 1);
+        let (key, value) = line.split_once('=').ok_or_else(|| error("expected key = value".to_string()))?;
+        let (key, value) = (key.trim().replace('_', "-"), value.trim());
+        match value {
+            "true" => args.push(vec![format!("--{key}")]),
+            "false" => {}
+            _ if value.starts_with('[') => {
+                let items = value.strip_prefix('[').and_then(|items| items.strip_suffix(']'))
+                    .ok_or_else(|| error(format!("arrays must be on one line, {value}")))?;
+                for item in array_items(items).into_iter().map(str::trim).filter(|item| !item.is_empty()) {
+                    args.push(vec![format!("--{key}"), parse_scalar(item).map_err(error)?]);
+                }
+            }
+            _ => {
+                args.push(vec![format!("--{key}"), parse_scalar(value).map_err(error)?]);
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Default flags from the config files, each with its value, to go before the arguments given on the command
+/// line so those win
+pub fn config_args() -> Result<Vec<Vec<String>>, String> {
+    let mut args = Vec::new();
+    for path in config_paths() {
+        let text = std::fs::read_to_string(&path).map_err(|error| format!("{}: {error}", path.display()))?;
+        args.extend(parse_config(&text).map_err(|error| format!("{}, {error}", path.display()))?);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(args: &[&[&str]]) -> Vec<Vec<String>> {
+        args.iter().map(|arg| arg.iter().map(|part| part.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn scalars_and_booleans() {
+        let text = "# defaults\nmax_solutions = 5\ncolor = true\nverbose = false\n\nstyle = \"unicode\"\n";
+        assert_eq!(parse_config(text).unwrap(), flags(&[&["--max-solutions", "5"], &["--color"], &["--style", "unicode"]]));
+    }
+
+    #[test]
+    fn arrays_keep_quoted_commas() {
+        let text = r#"restrict = ["r0c0=1,2", "r8c8=9", "say \"hi, there\""]"#;
+        assert_eq!(parse_config(text).unwrap(), flags(&[&["--restrict", "r0c0=1,2"], &["--restrict", "r8c8=9"], &["--restrict", "say \"hi, there\""]]));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(parse_config("color = true\nstyle").unwrap_err(), "line 2: expected key = value");
+        assert_eq!(parse_config("restrict = [\"r0c0=1\",").unwrap_err(), "line 1: arrays must be on one line, [\"r0c0=1\",");
+        assert_eq!(parse_config("style = \"unicode").unwrap_err(), "line 1: unterminated string \"unicode");
+        assert_eq!(parse_config("style = a b").unwrap_err(), "line 1: cannot read value a b");
+    }
+}
@@ -0,0 +1,50 @@
+use z3::Context;
+
+use crate::{print_candidate_grid, Sudoku};
+use crate::session::Session;
+
+fn digits(digits: &[u64]) -> String {
+    digits.iter().map(|digit| digit.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Prints the candidates every square lost or gained going from the original to the edited sudoku
+pub fn candidate_delta(original: &Sudoku, edited: &Sudoku, ctx: &Context) {
+    let before = Session::new(original, ctx).candidates();
+    let after = Session::new(edited, ctx).candidates();
+    if before.iter().flatten().all(|digits| digits.is_empty()) {
+        println!("The original sudoku has no solution.");
+    }
+    if after.iter().flatten().all(|digits| digits.is_empty()) {
+        println!("The edited sudoku has no solution.");
+    }
+
+    let mut eliminated = 0;
+    let mut reintroduced = 0;
+    let mut changed = Vec::new();
+    for (i, (old_row, new_row)) in before.iter().zip(&after).enumerate() {
+        for (j, (old, new)) in old_row.iter().zip(new_row).enumerate() {
+            let lost = old.iter().copied().filter(|digit| !new.contains(digit)).collect::<Vec<_>>();
+            let gained = new.iter().copied().filter(|digit| !old.contains(digit)).collect::<Vec<_>>();
+            if lost.is_empty() && gained.is_empty() {
+                continue;
+            }
+            let mut change = Vec::new();
+            if !lost.is_empty() {
+                change.push(format!("eliminated {}", digits(&lost)));
+            }
+            if !gained.is_empty() {
+                change.push(format!("reintroduced {}", digits(&gained)));
+            }
+            println!("Row {i} Column {j}: {}", change.join(", "));
+            eliminated += lost.len();
+            reintroduced += gained.len();
+            changed.push((i, j));
+        }
+    }
+    if changed.is_empty() {
+        println!("The edit does not change any candidates.");
+        return;
+    }
+    println!("{eliminated} candidates eliminated and {reintroduced} reintroduced across {} squares.", changed.len());
+    print_candidate_grid(&after, &changed);
+}
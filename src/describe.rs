@@ -0,0 +1,208 @@
+use crate::{CageRelation, Clue, CountRelation, MaskRule, OffsetRelation, Reading, Side, Sudoku};
+
+fn cell(cell: &[usize]) -> String {
+    format!("r{}c{}", cell[0], cell[1])
+}
+
+fn path(cells: &[Vec<usize>]) -> String {
+    cells.iter().map(|x| cell(x)).collect::<Vec<_>>().join("→")
+}
+
+fn clue(clue: &Clue) -> String {
+    match clue {
+        Clue::Known(value) => value.to_string(),
+        Clue::Unknown(name) => name.clone(),
+    }
+}
+
+fn reading(reading: &Reading) -> String {
+    let line = match reading.side {
+        Side::Left | Side::Right => "row",
+        Side::Top | Side::Bottom => "column",
+    };
+    format!("{line} {} read from the {}", reading.index, reading.side.name())
+}
+
+fn digits(digits: &[u64]) -> String {
+    digits.iter().map(|digit| digit.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Every constraint of the sudoku in human terms, one per line
+pub fn describe(sudoku: &Sudoku) -> Vec<String> {
+    let mut lines = Vec::new();
+    let size = sudoku.size();
+    lines.push(format!("{size}x{size} grid with digits {}", digits(&sudoku.digits)));
+    let units = [
+        (sudoku.horizontal_rule, "row"),
+        (sudoku.vertical_rule, "column"),
+        (sudoku.nonet_rule, "box"),
+        (sudoku.windoku, "windoku window"),
+        (sudoku.disjoint_groups, "disjoint group"),
+        (sudoku.positive_diagonal, "positive diagonal"),
+        (sudoku.negative_diagonal, "negative diagonal"),
+    ];
+    for (_, unit) in units.iter().filter(|(rule, _)| *rule) {
+        lines.push(format!("Digits do not repeat in a {unit}"));
+    }
+    let givens = sudoku.given.iter().flatten().filter(|given| given.is_some()).count();
+    lines.push(format!("{givens} givens"));
+    let filled = sudoku.state.filled.iter().flatten().filter(|filled| filled.is_some()).count();
+    if filled > 0 {
+        lines.push(format!("{filled} digits filled in"));
+    }
+    for mark in &sudoku.state.pencilmarks {
+        lines.push(format!("Pencilmarks {}: {}", cell(&mark.cell), digits(&mark.digits)));
+    }
+    for (i, region) in sudoku.digit_regions.iter().enumerate() {
+        lines.push(format!("Digit region {i}: {} only hold {}", path(&region.cells), digits(&region.digits)));
+    }
+    for (i, mask) in sudoku.masks.iter().enumerate() {
+        let rule = match &mask.rule {
+            MaskRule::Odd => "are odd".to_string(),
+            MaskRule::Even => "are even".to_string(),
+            MaskRule::AtLeast(value) => format!("are at least {value}"),
+            MaskRule::AtMost(value) => format!("are at most {value}"),
+            MaskRule::Distinct => "are distinct".to_string(),
+            MaskRule::Sum(sum) => format!("sum to {}", clue(sum)),
+        };
+        lines.push(format!("Mask {i}: {} {rule}", path(&mask.cells).replace('→', ", ")));
+    }
+    for (i, count) in sudoku.digit_counts.iter().enumerate() {
+        let relation = match count.relation {
+            CountRelation::Exactly => "exactly",
+            CountRelation::AtLeast => "at least",
+            CountRelation::AtMost => "at most",
+        };
+        lines.push(format!(
+            "Digit count {i}: {} appears {relation} {} times in {}", count.digit, clue(&count.count), path(&count.cells).replace('→', ", "),
+        ));
+    }
+    for (i, cage) in sudoku.killer_cages.iter().enumerate() {
+        let relation = match cage.relation {
+            CageRelation::Equal => "",
+            CageRelation::LessThan => "less than ",
+            CageRelation::GreaterThan => "more than ",
+        };
+        let sum = cage.sum.as_ref().map_or(String::new(), |sum| format!(" summing to {relation}{}", clue(sum)));
+        lines.push(format!("Killer cage {i}: {} distinct{sum}", path(&cage.cells).replace('→', ", ")));
+    }
+    if sudoku.distinct_cage_sums {
+        lines.push("No two killer cages have the same sum".to_string());
+    }
+    if sudoku.cage_anti_adjacency {
+        lines.push("Orthogonally adjacent squares across a cage boundary cannot contain the same digit".to_string());
+    }
+    if let Some(offset) = &sudoku.cage_sum_offset {
+        lines.push(format!("Killer cage clues are the sums plus {}", clue(offset)));
+    }
+    if let Some(modulo) = sudoku.cage_sum_modulo {
+        lines.push(format!("Killer cage clues are the sums modulo {modulo}"));
+    }
+    for unknown in &sudoku.unknowns {
+        lines.push(format!("Unknown {} between {} and {}", unknown.name, unknown.min, unknown.max));
+    }
+    if !sudoku.offset.is_empty() {
+        lines.push(format!("Squares at offsets {:?} do not repeat", sudoku.offset));
+    }
+    for group in &sudoku.offset_groups {
+        let relation = match group.relation {
+            OffsetRelation::NotEqual => "are not equal".to_string(),
+            OffsetRelation::NotConsecutive => "are not consecutive".to_string(),
+            OffsetRelation::AtLeast(difference) => format!("differ by at least {difference}"),
+        };
+        lines.push(format!("Offset group {}: squares at offsets {:?} {relation}", group.name, group.offsets));
+    }
+    if !sudoku.odd_shading.is_empty() {
+        lines.push(format!("Odd digits in exactly the {} shaded squares {}", sudoku.odd_shading.len(), path(&sudoku.odd_shading).replace('→', ", ")));
+    }
+    if !sudoku.odd_cells.is_empty() {
+        lines.push(format!("Odd digits in {}", path(&sudoku.odd_cells).replace('→', ", ")));
+    }
+    if !sudoku.even_cells.is_empty() {
+        lines.push(format!("Even digits in {}", path(&sudoku.even_cells).replace('→', ", ")));
+    }
+    for quadruple in &sudoku.quadruples {
+        lines.push(format!("Quadruple at the corner of {}: {} among them", path(&quadruple.cells()).replace('→', ", "), digits(&quadruple.digits)));
+    }
+    if sudoku.non_consecutive {
+        lines.push("Orthogonally adjacent squares never hold consecutive digits".to_string());
+    }
+    if sudoku.taxicab {
+        lines.push("A digit N is not N squares away from another N by taxicab distance".to_string());
+    }
+    for (i, thermo) in sudoku.thermo.iter().enumerate() {
+        lines.push(format!("Thermo {i}: {}", path(thermo)));
+    }
+    for (i, arrow) in sudoku.arrow.iter().enumerate() {
+        let (pill, shaft) = arrow.cells.split_at(arrow.pill.min(arrow.cells.len()));
+        let distinct = if arrow.distinct { ", shaft digits distinct" } else { "" };
+        lines.push(format!("Arrow {i}: circle {} equals the sum of {}{distinct}", path(pill), path(shaft)));
+    }
+    for kropki in &sudoku.kropki_adjacent {
+        lines.push(format!("White dot between {}", path(kropki).replace('→', " and ")));
+    }
+    for kropki in &sudoku.kropki_double {
+        lines.push(format!("Black dot between {}", path(kropki).replace('→', " and ")));
+    }
+    for kropki in &sudoku.kropki_ambiguous {
+        lines.push(format!("Grey dot between {}", path(kropki).replace('→', " and ")));
+    }
+    if let Some(bars) = &sudoku.consecutive_bars {
+        for bar in bars {
+            lines.push(format!("Consecutive bar between {}", path(bar).replace('→', " and ")));
+        }
+        lines.push("No other adjacent squares are consecutive".to_string());
+    }
+    for (i, whisper) in sudoku.german_whispers.iter().enumerate() {
+        lines.push(format!("German whisper {i}: {}, neighbours differ by at least {}", path(whisper), clue(&sudoku.whisper_difference)));
+    }
+    for (i, renban) in sudoku.renban.iter().enumerate() {
+        lines.push(format!("Renban {i}: {}, digits are distinct and consecutive in any order", path(renban)));
+    }
+    for (i, lockout) in sudoku.lockout_lines.iter().enumerate() {
+        lines.push(format!("Lockout line {i}: {}, ends differ by at least 4 and no digit between them lies in their range", path(lockout)));
+    }
+    for (i, entropic) in sudoku.entropic_lines.iter().enumerate() {
+        lines.push(format!("Entropic line {i}: {}, every three squares in a row hold a low, a middle and a high digit", path(entropic)));
+    }
+    for (i, modular) in sudoku.modular_lines.iter().enumerate() {
+        lines.push(format!("Modular line {i}: {}, every three squares in a row hold digits with different remainders modulo 3", path(modular)));
+    }
+    for (i, line) in sudoku.region_sum_lines.iter().enumerate() {
+        lines.push(format!("Region sum line {i}: {}, the segments in each box have the same sum", path(line)));
+    }
+    for square in &sudoku.row_indexing {
+        lines.push(format!("Row indexing from {}", cell(square)));
+    }
+    for square in &sudoku.column_indexing {
+        lines.push(format!("Column indexing from {}", cell(square)));
+    }
+    for sandwich in &sudoku.sandwich {
+        lines.push(format!(
+            "Sandwich {} {}: digits between {} and {} sum to {}",
+            sandwich.side.name(), sandwich.index, sandwich.crusts[0], sandwich.crusts[1], clue(&sandwich.sum),
+        ));
+    }
+    for frame in &sudoku.frame {
+        lines.push(format!("Frame {} {}: first squares sum to {}", frame.side.name(), frame.index, clue(&frame.sum)));
+    }
+    for skyscraper in &sudoku.skyscraper {
+        lines.push(format!("Skyscraper {} {}: {} digits visible", skyscraper.side.name(), skyscraper.index, clue(&skyscraper.count)));
+    }
+    for x_sum in &sudoku.x_sum {
+        lines.push(format!("X-sum {} {}: as many squares as the first digit sum to {}", x_sum.side.name(), x_sum.index, clue(&x_sum.sum)));
+    }
+    for order in &sudoku.line_order {
+        lines.push(format!("Line order: {} is less than {}", reading(&order.lesser), reading(&order.greater)));
+    }
+    for square in &sudoku.unknown_givens {
+        lines.push(format!("Unknown given at {}", cell(square)));
+    }
+    if !sudoku.fog.is_empty() {
+        lines.push(format!("{} squares start in fog", sudoku.fog.len()));
+    }
+    for (i, soft) in sudoku.soft.iter().enumerate() {
+        lines.push(format!("Soft constraint {i}: {} on {}, weight {}", soft.rule.name(), path(&soft.cells), soft.weight));
+    }
+    lines
+}
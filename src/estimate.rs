@@ -0,0 +1,103 @@
+use z3::{Context, Solver};
+use z3::ast::{Ast, Int, Bool};
+
+use crate::{count_up_to, Sudoku};
+use crate::rng::Rng;
+
+/// Number of solutions a hashed cell has to fall below in sample mode, about the threshold for a tolerance of 0.8
+pub const PIVOT: u32 = 72;
+
+/// Number of solutions a hashed cell has to fall below before it is scaled up, as ApproxMC picks it so the
+/// estimate is within a factor of 1 + epsilon
+pub fn threshold(epsilon: f64) -> u32 {
+    (1.0 + 9.84 * (1.0 + epsilon / (1.0 + epsilon)) * (1.0 + 1.0 / epsilon).powi(2)).ceil() as u32
+}
+
+/// Number of trials whose median is within the tolerance with probability at least 1 - delta
+pub fn trial_count(delta: f64) -> u32 {
+    (17.0 * (3.0 / delta).log2()).ceil() as u32
+}
+
+pub fn value_bits<'ctx>(sudoku: &Sudoku, grid: &[Vec<Int<'ctx>>], ctx: &'ctx Context) -> Vec<Bool<'ctx>> {
+    let mut bits = Vec::new();
+    for row in grid {
+        for cell in row {
+            for &k in &sudoku.digits {
+                bits.push(cell._eq(&Int::from_u64(ctx, k)));
+            }
+        }
+    }
+    bits
+}
+
+pub fn random_xor<'ctx>(bits: &[Bool<'ctx>], rng: &mut Rng, ctx: &'ctx Context) -> Bool<'ctx> {
+    let mut xor = Bool::from_bool(ctx, rng.next_bool());
+    for bit in bits {
+        if rng.next_bool() {
+            xor = xor.xor(bit);
+        }
+    }
+    xor
+}
+
+/// Approximate model counting as in ApproxMC: the median of the trials is within a factor of 1 + epsilon of
+/// the number of solutions with probability at least 1 - delta
+pub fn estimate_solutions(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], solver: &Solver, ctx: &Context, epsilon: f64, delta: f64, seed: u64) {
+    let threshold = threshold(epsilon);
+    let exact = count_up_to(grid, solver, ctx, threshold);
+    if exact == 0 {
+        println!("Could not find a satisfying sudoku.");
+        return;
+    }
+    if exact < threshold {
+        println!("Found exactly {exact} possible sudokus!");
+        return;
+    }
+
+    let trials = trial_count(delta);
+    println!("Found at least {threshold} sudokus, hashing {trials} times with seed {seed}...");
+    let bits = value_bits(sudoku, grid, ctx);
+    let mut rng = Rng::new(seed);
+    let mut estimates = Vec::new();
+    for trial in 1..=trials {
+        solver.push();
+        for m in 1..=bits.len() {
+            solver.assert(&random_xor(&bits, &mut rng, ctx));
+            let count = count_up_to(grid, solver, ctx, threshold);
+            if count < threshold {
+                if count == 0 {
+                    println!("Trial {trial}: hashed away every solution, discarding");
+                } else {
+                    let estimate = count as f64 * 2f64.powi(m as i32);
+                    println!("Trial {trial}: {count} sudokus left after {m} XOR constraints, ~{estimate:.0} in total");
+                    estimates.push(estimate);
+                }
+                break;
+            }
+        }
+        solver.pop(1);
+    }
+
+    if estimates.is_empty() {
+        println!("Every trial failed, try again with more trials or another seed.");
+        return;
+    }
+    estimates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = estimates[estimates.len() / 2];
+    let confidence = ((1.0 - delta) * 1e4).round() / 1e2;
+    println!("Estimated ~{median:.0} possible sudokus, between {:.0} and {:.0} with probability at least {confidence}%",
+        median / (1.0 + epsilon), median * (1.0 + epsilon));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approxmc_parameters() {
+        assert_eq!(threshold(0.8), 73);
+        assert_eq!(threshold(1.0), 61);
+        assert_eq!(trial_count(0.2), 67);
+        assert_eq!(trial_count(0.5), 44);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{parse_sudoku, Sudoku};
+use crate::session::Session;
+
+/// A reference puzzle with its known number of solutions and the candidates of every square
+pub struct Fixture {
+    pub name: &'static str,
+    pub json: &'static str,
+    pub solutions: u32,
+    /// One line per row, candidates of each square separated by spaces
+    pub candidates: &'static str,
+}
+
+/// Reference puzzles for checking an integration against known results, as `verify` does
+///
+/// ```
+/// use sudoku_z3::Session;
+/// use sudoku_z3::fixtures::FIXTURES;
+///
+/// let config = z3::Config::new();
+/// let ctx = z3::Context::new(&config);
+/// for fixture in &FIXTURES {
+///     let session = Session::new(&fixture.sudoku(), &ctx);
+///     assert_eq!(session.count(fixture.solutions + 1), fixture.solutions);
+///     assert_eq!(session.candidates(), fixture.candidate_grid());
+/// }
+/// ```
+pub const FIXTURES: [Fixture; 3] = [
+    Fixture {
+        name: "classic",
+        json: include_str!("../fixtures/classic.json"),
+        solutions: 4,
+        candidates: "89 16 24 47 25 39 57 36 18
+89 16 24 47 25 39 57 36 18
+3 7 5 6 1 8 2 4 9
+6 4 9 8 7 5 3 1 2
+7 2 1 9 3 6 8 5 4
+5 3 8 2 4 1 6 9 7
+4 8 6 5 9 7 1 2 3
+1 9 7 3 6 2 4 8 5
+2 5 3 1 8 4 9 7 6",
+    },
+    Fixture {
+        name: "lines",
+        json: include_str!("../fixtures/lines.json"),
+        solutions: 18,
+        candidates: "89 67 45 467 12 389 25 346 189
+389 1 2 46 5 389 7 46 89
+389 67 45 467 12 389 25 346 189
+6 4 9 8 7 5 3 1 2
+7 2 1 9 3 6 8 5 4
+5 3 8 2 4 1 6 9 7
+4 8 6 5 9 7 1 2 3
+1 9 7 3 6 2 4 8 5
+2 5 3 1 8 4 9 7 6",
+    },
+    Fixture {
+        name: "six",
+        json: include_str!("../fixtures/six.json"),
+        solutions: 2,
+        candidates: "1 2 3 4 5 6
+4 5 6 1 2 3
+2 3 1 6 4 5
+6 4 5 23 13 12
+3 1 2 5 6 4
+5 6 4 23 13 12",
+    },
+];
+
+impl Fixture {
+    pub fn sudoku(&self) -> Sudoku {
+        parse_sudoku(&serde_json::from_str(self.json).unwrap())
+    }
+
+    /// The candidates of every square, in the shape Session::candidates returns them
+    pub fn candidate_grid(&self) -> Vec<Vec<Vec<u64>>> {
+        self.candidates.lines().map(|row| row.split(' ').map(
+            |square| square.chars().map(|digit| digit.to_digit(10).unwrap() as u64).collect()
+        ).collect()).collect()
+    }
+}
+
+/// Solves every fixture and checks the results, returning whether all of them passed
+pub fn verify_install() -> bool {
+    let mut passed = true;
+    for fixture in &FIXTURES {
+        let sudoku = fixture.sudoku();
+        let config = z3::Config::new();
+        let ctx = z3::Context::new(&config);
+        let session = Session::new(&sudoku, &ctx);
+
+        let solutions = session.count(fixture.solutions + 1);
+        if solutions != fixture.solutions {
+            println!("{}: FAILED, expected {} solutions but found {solutions}", fixture.name, fixture.solutions);
+            passed = false;
+        } else if session.candidates() != fixture.candidate_grid() {
+            println!("{}: FAILED, candidates differ from the expected ones", fixture.name);
+            passed = false;
+        } else {
+            println!("{}: ok", fixture.name);
+        }
+    }
+    passed
+}
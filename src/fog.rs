@@ -0,0 +1,117 @@
+use z3::{Context, SatResult, Solver};
+use z3::ast::{Ast, Bool, Int};
+
+use crate::{print_candidate_grid, Sudoku};
+use crate::solver::add_solver_constraints;
+
+/// The sudoku as a solver can see it, only the placed digits and clues lying entirely in revealed squares are kept
+fn visible(sudoku: &Sudoku, revealed: &[Vec<bool>], placed: &[Vec<Option<u64>>]) -> Sudoku {
+    let seen = |line: &Vec<Vec<usize>>| line.iter().all(|cell| revealed[cell[0]][cell[1]]);
+    let mut visible = sudoku.clone();
+    visible.given = placed.to_vec();
+    visible.thermo.retain(seen);
+    visible.arrow.retain(|arrow| seen(&arrow.cells));
+    visible.kropki_adjacent.retain(seen);
+    visible.kropki_double.retain(seen);
+    visible.kropki_ambiguous.retain(seen);
+    visible.german_whispers.retain(seen);
+    visible.renban.retain(seen);
+    visible.lockout_lines.retain(seen);
+    visible.entropic_lines.retain(seen);
+    visible.modular_lines.retain(seen);
+    visible.region_sum_lines.retain(seen);
+    visible.digit_regions.retain(|region| seen(&region.cells));
+    visible.odd_cells.retain(|cell| revealed[cell[0]][cell[1]]);
+    visible.even_cells.retain(|cell| revealed[cell[0]][cell[1]]);
+    visible.quadruples.retain(|quadruple| seen(&quadruple.cells()));
+    visible.masks.retain(|mask| seen(&mask.cells));
+    visible.killer_cages.retain(|cage| seen(&cage.cells));
+    visible.digit_counts.retain(|count| seen(&count.cells));
+    if revealed.iter().flatten().any(|&revealed| !revealed) {
+        visible.consecutive_bars = None;
+        visible.odd_shading.clear();
+    }
+    visible
+}
+
+fn reveal_around(revealed: &mut [Vec<bool>], i: usize, j: usize) -> usize {
+    let mut count = 0;
+    for row in revealed.iter_mut().skip(i.saturating_sub(1)).take(if i == 0 { 2 } else { 3 }) {
+        for square in row.iter_mut().skip(j.saturating_sub(1)).take(if j == 0 { 2 } else { 3 }) {
+            if !*square {
+                *square = true;
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+pub fn simulate_fog(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], ctx: &Context) {
+    let size = grid.len();
+    let mut revealed = vec![vec![true; size]; size];
+    for cell in &sudoku.fog {
+        revealed[cell[0]][cell[1]] = false;
+    }
+    let mut placed = vec![vec![None; size]; size];
+
+    let mut stage = 0;
+    loop {
+        stage += 1;
+        for i in 0..size {
+            for j in 0..size {
+                if revealed[i][j] && placed[i][j].is_none() {
+                    placed[i][j] = sudoku.given[i][j];
+                }
+            }
+        }
+        let current = visible(sudoku, &revealed, &placed);
+        let solver = Solver::new(ctx);
+        add_solver_constraints(&current, grid, &solver, ctx);
+        let model = match solver.check() {
+            SatResult::Sat => solver.get_model().unwrap(),
+            SatResult::Unsat => {
+                println!("Stage {stage}: the revealed clues contradict each other.");
+                return;
+            }
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        };
+
+        let mut deductions = Vec::new();
+        for i in 0..size {
+            for j in 0..size {
+                if !revealed[i][j] || placed[i][j].is_some() {
+                    continue;
+                }
+                let value = model.get_const_interp(&grid[i][j]).unwrap().as_u64().unwrap();
+                solver.push();
+                solver.assert(&Bool::not(&grid[i][j]._eq(&Int::from_u64(ctx, value))));
+                if solver.check() == SatResult::Unsat {
+                    deductions.push((i, j, value));
+                }
+                solver.pop(1);
+            }
+        }
+        if deductions.is_empty() {
+            break;
+        }
+
+        let mut uncovered = 0;
+        for &(i, j, value) in &deductions {
+            placed[i][j] = Some(value);
+            uncovered += reveal_around(&mut revealed, i, j);
+        }
+        println!("Stage {stage}: placed {} digits, revealed {uncovered} squares", deductions.len());
+    }
+
+    let unsolved = placed.iter().flatten().filter(|digit| digit.is_none()).count();
+    let fogged = revealed.iter().flatten().filter(|&&revealed| !revealed).count();
+    if unsolved == 0 {
+        println!("The sudoku can be solved through the fog.");
+    } else {
+        println!("Stuck with {unsolved} squares unsolved, {fogged} of them still in fog.");
+    }
+    let candidates = placed.iter().map(|row| row.iter().map(|digit| digit.iter().copied().collect()).collect()).collect::<Vec<Vec<Vec<u64>>>>();
+    let fog = (0..size).flat_map(|i| (0..size).map(move |j| (i, j))).filter(|&(i, j)| !revealed[i][j]).collect::<Vec<_>>();
+    print_candidate_grid(&candidates, &fog);
+}
@@ -0,0 +1,65 @@
+use std::fmt::Write;
+
+use crate::{box_shape, print_boxed, Heatmap};
+
+/// Green for a square down to one candidate through to red for a square with every digit left, grey for none
+fn colour(count: usize, digits: usize) -> (u8, u8, u8) {
+    if count == 0 {
+        return (128, 128, 128);
+    }
+    let t = (count - 1) as f64 / (digits.max(2) - 1) as f64;
+    let mix = |from: f64, to: f64| (from + (to - from) * t.min(1.0)).round() as u8;
+    (mix(70.0, 220.0), mix(180.0, 60.0), mix(90.0, 50.0))
+}
+
+fn print_ansi(candidates: &[Vec<Vec<u64>>], digits: usize) {
+    let cells = candidates.iter().map(|row| row.iter().map(|square| {
+        let (r, g, b) = colour(square.len(), digits);
+        format!("\x1b[48;2;{r};{g};{b}m\x1b[30m{:>2}\x1b[0m", square.len())
+    }).collect()).collect::<Vec<_>>();
+    print_boxed(&cells, 2);
+}
+
+fn heatmap_svg(candidates: &[Vec<Vec<u64>>], digits: usize) -> String {
+    const SQUARE: usize = 40;
+    let size = candidates.len();
+    let (box_height, box_width) = box_shape(size);
+    let side = size * SQUARE;
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{side}\" height=\"{side}\" viewBox=\"0 0 {side} {side}\">\n");
+    for (i, row) in candidates.iter().enumerate() {
+        for (j, square) in row.iter().enumerate() {
+            let (r, g, b) = colour(square.len(), digits);
+            let (x, y) = (j * SQUARE, i * SQUARE);
+            writeln!(svg, "  <rect x=\"{x}\" y=\"{y}\" width=\"{SQUARE}\" height=\"{SQUARE}\" fill=\"rgb({r},{g},{b})\" stroke=\"#888\"/>").unwrap();
+            writeln!(
+                svg, "  <text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"16\" text-anchor=\"middle\">{}</text>",
+                x + SQUARE / 2, y + SQUARE * 3 / 5, square.len(),
+            ).unwrap();
+        }
+    }
+    for k in 0..=size {
+        if k % box_height == 0 {
+            writeln!(svg, "  <line x1=\"0\" y1=\"{0}\" x2=\"{side}\" y2=\"{0}\" stroke=\"black\" stroke-width=\"3\"/>", k * SQUARE).unwrap();
+        }
+        if k % box_width == 0 {
+            writeln!(svg, "  <line x1=\"{0}\" y1=\"0\" x2=\"{0}\" y2=\"{side}\" stroke=\"black\" stroke-width=\"3\"/>", k * SQUARE).unwrap();
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Shows how many candidates every square has left, in the terminal or as SVG written to the output file
+pub fn show_heatmap(candidates: &[Vec<Vec<u64>>], digits: usize, heatmap: Option<Heatmap>, output: Option<&str>) {
+    match heatmap {
+        None => {}
+        Some(Heatmap::Ansi) => print_ansi(candidates, digits),
+        Some(Heatmap::Svg) => {
+            let svg = heatmap_svg(candidates, digits);
+            match output {
+                Some(path) => std::fs::write(path, svg).unwrap(),
+                None => print!("{svg}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, Scope};
+use std::time::{Duration, Instant};
+
+use z3::{Context, ContextHandle};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Cancels every token and makes every later query count as interrupted, as Ctrl-C does in the command line
+/// tool. It only sets a flag, so it can be called from a signal handler.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the process has been interrupted
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Whether a query came back unknown because of an interrupt. Z3 catches Ctrl-C itself while a query runs and
+/// reports it as canceled, so the reason is checked as well as the flag set by interrupt between queries.
+pub fn interrupted_query(reason: Option<String>) -> bool {
+    if matches!(reason.as_deref(), Some("canceled" | "interrupted from keyboard")) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    interrupted()
+}
+
+/// State shared by the clones of a token and its watcher
+struct Shared<'ctx> {
+    cancelled: AtomicBool,
+    deadline: Mutex<Option<Instant>>,
+    handle: ContextHandle<'ctx>,
+}
+
+/// Cancels the queries of a context from any thread, for deadlines and user requests alike. Cancelling sticks:
+/// the query running at the time returns unknown and so does every later one. Clones cancel the same context.
+///
+/// ```
+/// use std::time::Duration;
+/// use sudoku_z3::CancelToken;
+///
+/// let config = z3::Config::new();
+/// let ctx = z3::Context::new(&config);
+/// std::thread::scope(|scope| {
+///     let cancel = CancelToken::new(&ctx, scope);
+///     cancel.cancel_after(Duration::from_secs(60));
+///     assert!(!cancel.is_cancelled());
+///     cancel.clone().cancel();
+///     assert!(cancel.is_cancelled());
+/// });
+/// ```
+#[derive(Clone)]
+pub struct CancelToken<'ctx> {
+    shared: Arc<Shared<'ctx>>,
+}
+
+impl<'ctx> CancelToken<'ctx> {
+    /// A token for the context, with a watcher thread in the scope that passes on interrupts and deadlines and
+    /// keeps interrupting once cancelled, since a query started after a single interrupt would run to the
+    /// end. The watcher stops once every clone of the token is dropped, so the scope can end.
+    pub fn new<'scope>(ctx: &'ctx Context, scope: &'scope Scope<'scope, '_>) -> CancelToken<'ctx> where 'ctx: 'scope {
+        let shared = Arc::new(Shared { cancelled: AtomicBool::new(false), deadline: Mutex::new(None), handle: ctx.handle() });
+        let watched = Arc::downgrade(&shared);
+        scope.spawn(move || {
+            while let Some(shared) = watched.upgrade() {
+                let token = CancelToken { shared };
+                let due = token.shared.deadline.lock().unwrap().is_some_and(|deadline| Instant::now() >= deadline);
+                if interrupted() || due {
+                    token.cancel();
+                } else if token.is_cancelled() {
+                    token.shared.handle.interrupt();
+                }
+                drop(token);
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        CancelToken { shared }
+    }
+
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.shared.handle.interrupt();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancels once the duration has passed, without blocking
+    pub fn cancel_after(&self, duration: Duration) {
+        *self.shared.deadline.lock().unwrap() = Some(Instant::now() + duration);
+    }
+}
@@ -0,0 +1,1359 @@
+#![recursion_limit = "256"]
+
+pub mod solver;
+pub mod optimize;
+pub mod estimate;
+pub mod rng;
+pub mod orient;
+pub mod normalize;
+pub mod clues;
+pub mod objective;
+pub mod placement;
+pub mod fog;
+pub mod fixtures;
+pub mod describe;
+pub mod delta;
+pub mod logic;
+pub mod chain;
+pub mod sample;
+pub mod profile;
+pub mod parallel;
+pub mod session;
+pub mod mistakes;
+pub mod trainer;
+pub mod heatmap;
+pub mod projection;
+pub mod multigrid;
+pub mod interrupt;
+pub mod relabel;
+pub mod analyze;
+pub mod ranges;
+pub mod sudokupad;
+#[cfg(feature = "sat")]
+pub mod sat;
+
+use std::{collections::HashMap, fs::File, io::{BufReader, IsTerminal}, path::{Path, PathBuf}, sync::OnceLock};
+use clap::ValueEnum;
+use serde_json::*;
+use z3::{Context, SatResult, Solver, Model};
+use z3::ast::{Ast, Int, Bool};
+
+use crate::interrupt::interrupted_query;
+
+pub use crate::interrupt::CancelToken;
+pub use crate::session::Session;
+
+#[derive(Debug, Clone)]
+pub struct Sudoku {
+    pub digits: Vec<u64>,
+    pub digit_regions: Vec<DigitRegion>,
+    pub given: Vec<Vec<Option<u64>>>,
+    pub unknown_givens: Vec<Vec<usize>>,
+    pub fog: Vec<Vec<usize>>,
+    pub horizontal_rule: bool,
+    pub vertical_rule: bool,
+    pub nonet_rule: bool,
+    /// Digits do not repeat in the windoku windows
+    pub windoku: bool,
+    /// Digits do not repeat among squares in the same position of every box
+    pub disjoint_groups: bool,
+    /// Digits do not repeat on the diagonal from the bottom left to the top right
+    pub positive_diagonal: bool,
+    /// Digits do not repeat on the diagonal from the top left to the bottom right
+    pub negative_diagonal: bool,
+    pub offset: Vec<Vec<i32>>,
+    pub offset_groups: Vec<OffsetGroup>,
+    pub taxicab: bool,
+    /// Orthogonally adjacent squares never hold consecutive digits
+    pub non_consecutive: bool,
+    /// The squares holding odd digits, every other square holds an even digit
+    pub odd_shading: Vec<Vec<usize>>,
+    /// Squares marked with a circle, which hold odd digits
+    pub odd_cells: Vec<Vec<usize>>,
+    /// Squares marked with a square, which hold even digits
+    pub even_cells: Vec<Vec<usize>>,
+    pub quadruples: Vec<Quadruple>,
+    pub thermo: Vec<Vec<Vec<usize>>>,
+    pub arrow: Vec<Arrow>,
+    pub kropki_adjacent: Vec<Vec<Vec<usize>>>,
+    pub kropki_double: Vec<Vec<Vec<usize>>>,
+    pub kropki_ambiguous: Vec<Vec<Vec<usize>>>,
+    pub consecutive_bars: Option<Vec<Vec<Vec<usize>>>>,
+    pub german_whispers: Vec<Vec<Vec<usize>>>,
+    /// Lines whose digits are distinct and consecutive in any order
+    pub renban: Vec<Vec<Vec<usize>>>,
+    /// Lines whose ends differ by at least 4 and whose other digits lie outside the range the ends span
+    pub lockout_lines: Vec<Vec<Vec<usize>>>,
+    /// Lines where every three squares in a row hold a low, a middle and a high digit
+    pub entropic_lines: Vec<Vec<Vec<usize>>>,
+    /// Lines where every three squares in a row hold digits with different remainders modulo 3
+    pub modular_lines: Vec<Vec<Vec<usize>>>,
+    /// Lines whose segments in each box they pass through all have the same sum
+    pub region_sum_lines: Vec<Vec<Vec<usize>>>,
+    pub row_indexing: Vec<Vec<usize>>,
+    pub column_indexing: Vec<Vec<usize>>,
+    pub whisper_difference: Clue,
+    pub sandwich: Vec<Sandwich>,
+    pub frame: Vec<Frame>,
+    pub skyscraper: Vec<Skyscraper>,
+    pub x_sum: Vec<XSum>,
+    pub unknowns: Vec<Unknown>,
+    pub soft: Vec<SoftConstraint>,
+    pub masks: Vec<Mask>,
+    pub killer_cages: Vec<Cage>,
+    /// Whether no two killer cages may have the same sum
+    pub distinct_cage_sums: bool,
+    /// Whether orthogonally adjacent squares on either side of a cage boundary must differ
+    pub cage_anti_adjacency: bool,
+    /// For mystery killers, the number every cage clue is the sum modulo
+    pub cage_sum_modulo: Option<u64>,
+    /// For mystery killers, the amount added to the sum of every cage to give its clue
+    pub cage_sum_offset: Option<Clue>,
+    pub line_order: Vec<LineOrder>,
+    pub digit_counts: Vec<DigitCount>,
+    pub state: State,
+    /// Prefix of the names of the squares and auxiliary constants, set for the grids of a hunt so that each
+    /// grid in the shared solver gets its own
+    pub prefix: String,
+    /// Whether the solver starts from the candidates left by human techniques, as set by --prune
+    pub prune: bool,
+}
+
+impl Sudoku {
+    /// Number of rows and columns, one for every digit
+    pub fn size(&self) -> usize {
+        self.digits.len()
+    }
+
+    /// Smallest and largest digit, the default crusts of a sandwich
+    pub fn crusts(&self) -> [u64; 2] {
+        [self.digits[0], self.digits[self.digits.len() - 1]]
+    }
+
+    /// Whether every number between the smallest and largest digit is a digit
+    pub fn contiguous(&self) -> bool {
+        self.digits.is_empty() || self.digits[self.digits.len() - 1] - self.digits[0] + 1 == self.digits.len() as u64
+    }
+
+    /// Every clue of the sudoku that can name an unknown
+    pub fn clues_mut(&mut self) -> Vec<&mut Clue> {
+        let mut clues = vec![&mut self.whisper_difference];
+        clues.extend(self.sandwich.iter_mut().map(|sandwich| &mut sandwich.sum));
+        clues.extend(self.frame.iter_mut().map(|frame| &mut frame.sum));
+        clues.extend(self.skyscraper.iter_mut().map(|skyscraper| &mut skyscraper.count));
+        clues.extend(self.x_sum.iter_mut().map(|x_sum| &mut x_sum.sum));
+        clues.extend(self.killer_cages.iter_mut().filter_map(|cage| cage.sum.as_mut()));
+        clues.extend(self.cage_sum_offset.as_mut());
+        clues.extend(self.masks.iter_mut().filter_map(|mask| match &mut mask.rule {
+            MaskRule::Sum(sum) => Some(sum),
+            _ => None,
+        }));
+        clues.extend(self.digit_counts.iter_mut().map(|count| &mut count.count));
+        clues
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DigitRegion {
+    pub cells: Vec<Vec<usize>>,
+    pub digits: Vec<u64>,
+}
+
+/// Progress of a solver on the sudoku, on top of the givens
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    /// Digits the solver has placed, in the same shape as the givens
+    pub filled: Vec<Vec<Option<u64>>>,
+    pub pencilmarks: Vec<Pencilmark>,
+}
+
+/// A circle on the corner shared by four squares, listing digits that must all appear among them
+#[derive(Debug, Clone)]
+pub struct Quadruple {
+    /// The top left of the four squares
+    pub cell: Vec<usize>,
+    /// Digits that must appear, repeated when they appear more than once
+    pub digits: Vec<u64>,
+}
+
+impl Quadruple {
+    pub fn cells(&self) -> Vec<Vec<usize>> {
+        [(0, 0), (0, 1), (1, 0), (1, 1)].iter().map(|(di, dj)| vec![self.cell[0] + di, self.cell[1] + dj]).collect()
+    }
+}
+
+/// Digits the solver has narrowed a square down to
+#[derive(Debug, Clone)]
+pub struct Pencilmark {
+    pub cell: Vec<usize>,
+    pub digits: Vec<u64>,
+}
+
+/// Height and width of the boxes of a grid, as close to square as the size allows
+fn box_shape(size: usize) -> (usize, usize) {
+    let height = (1..=size).filter(|&h| size.is_multiple_of(h) && h * h <= size).max().unwrap_or(1);
+    (height, size / height)
+}
+
+/// Squares of every box, boxes left to right then top to bottom
+fn nonets(size: usize) -> Vec<Vec<(usize, usize)>> {
+    let (height, width) = box_shape(size);
+    (0..size).map(|b| {
+        let (top, left) = (b / (size / width) * height, b % (size / width) * width);
+        (top..top + height).flat_map(|i| (left..left + width).map(move |j| (i, j))).collect()
+    }).collect()
+}
+
+/// Squares in the same position of every box, one group for each position within a box
+fn disjoint_groups(size: usize) -> Vec<Vec<(usize, usize)>> {
+    let boxes = nonets(size);
+    (0..size).map(|k| boxes.iter().map(|squares| squares[k]).collect()).collect()
+}
+
+/// Squares of the windoku windows, boxes of the usual shape set one square apart from each other and the edge
+fn windows(size: usize) -> Vec<Vec<(usize, usize)>> {
+    let (height, width) = box_shape(size);
+    let starts = |extent: usize| (0..).map(move |k| 1 + k * (extent + 1)).take_while(move |start| start + extent < size);
+    starts(height).flat_map(|top| starts(width).map(move |left| {
+        (top..top + height).flat_map(|i| (left..left + width).map(move |j| (i, j))).collect()
+    })).collect()
+}
+
+/// A line cut where it crosses from one box to another, each visit to a box being its own segment
+fn box_segments(line: &[Vec<usize>], size: usize) -> Vec<&[Vec<usize>]> {
+    let (height, width) = box_shape(size);
+    line.chunk_by(|a, b| (a[0] / height, a[1] / width) == (b[0] / height, b[1] / width)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Arrow {
+    pub cells: Vec<Vec<usize>>,
+    /// Number of squares at the start of the arrow read together as a number
+    pub pill: usize,
+    pub distinct: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct OffsetGroup {
+    pub name: String,
+    pub offsets: Vec<Vec<i32>>,
+    pub relation: OffsetRelation,
+}
+
+#[derive(Debug, Clone)]
+pub enum OffsetRelation {
+    NotEqual,
+    NotConsecutive,
+    AtLeast(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// Squares of a row or column as seen from this side of the grid, nearest first
+    pub fn line(self, index: usize, size: usize) -> Vec<(usize, usize)> {
+        match self {
+            Side::Left => (0..size).map(|j| (index, j)).collect(),
+            Side::Right => (0..size).rev().map(|j| (index, j)).collect(),
+            Side::Top => (0..size).map(|i| (i, index)).collect(),
+            Side::Bottom => (0..size).rev().map(|i| (i, index)).collect(),
+        }
+    }
+
+    /// Squares of the box-deep frame of a row or column as seen from this side of the grid, nearest first
+    pub fn frame(self, index: usize, size: usize) -> Vec<(usize, usize)> {
+        let (box_height, box_width) = box_shape(size);
+        let depth = match self {
+            Side::Left | Side::Right => box_width,
+            Side::Top | Side::Bottom => box_height,
+        };
+        self.line(index, size).into_iter().take(depth).collect()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+            Side::Top => "top",
+            Side::Bottom => "bottom",
+        }
+    }
+}
+
+/// A clue that is either written in the puzzle or refers to one of its named unknowns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clue {
+    Known(u64),
+    Unknown(String),
+}
+
+impl Clue {
+    pub fn ast<'ctx>(&self, ctx: &'ctx Context) -> Int<'ctx> {
+        match self {
+            Clue::Known(value) => Int::from_u64(ctx, *value),
+            Clue::Unknown(name) => Int::new_const(ctx, name.as_str()),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Clue::Known(value) => Value::from(*value),
+            Clue::Unknown(name) => Value::from(name.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Unknown {
+    pub name: String,
+    pub min: i64,
+    pub max: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sandwich {
+    pub side: Side,
+    pub index: usize,
+    pub sum: Clue,
+    pub crusts: [u64; 2],
+}
+
+/// A row or column read as one number starting from a side of the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reading {
+    pub side: Side,
+    pub index: usize,
+}
+
+/// Two lines read as numbers, the first smaller than the second
+#[derive(Debug, Clone)]
+pub struct LineOrder {
+    pub lesser: Reading,
+    pub greater: Reading,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub side: Side,
+    pub index: usize,
+    pub sum: Clue,
+}
+
+/// A row or column seen from a side of the grid, where a digit is visible when it is larger than every digit
+/// before it
+#[derive(Debug, Clone)]
+pub struct Skyscraper {
+    pub side: Side,
+    pub index: usize,
+    pub count: Clue,
+}
+
+/// A row or column seen from a side of the grid, where the first digit says how many squares to add up
+#[derive(Debug, Clone)]
+pub struct XSum {
+    pub side: Side,
+    pub index: usize,
+    pub sum: Clue,
+}
+
+#[derive(Debug, Clone)]
+pub struct SoftConstraint {
+    pub rule: SoftRule,
+    pub cells: Vec<Vec<usize>>,
+    pub weight: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoftRule {
+    Given(u64),
+    Thermo,
+    Arrow,
+    GermanWhisper,
+    KropkiAdjacent,
+    KropkiDouble,
+    KropkiAmbiguous,
+}
+
+impl SoftRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SoftRule::Given(_) => "given",
+            SoftRule::Thermo => "thermo",
+            SoftRule::Arrow => "arrow",
+            SoftRule::GermanWhisper => "germanWhisper",
+            SoftRule::KropkiAdjacent => "kropkiAdjacent",
+            SoftRule::KropkiDouble => "kropkiDouble",
+            SoftRule::KropkiAmbiguous => "kropkiAmbiguous",
+        }
+    }
+}
+
+/// Squares holding distinct digits, summing to the clue when there is one
+#[derive(Debug, Clone)]
+pub struct Cage {
+    pub cells: Vec<Vec<usize>>,
+    pub sum: Option<Clue>,
+    pub relation: CageRelation,
+}
+
+/// How the sum of a killer cage compares with its clue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CageRelation {
+    Equal,
+    LessThan,
+    GreaterThan,
+}
+
+impl CageRelation {
+    pub fn name(self) -> &'static str {
+        match self {
+            CageRelation::Equal => "equal",
+            CageRelation::LessThan => "lessThan",
+            CageRelation::GreaterThan => "greaterThan",
+        }
+    }
+}
+
+/// Auxiliary constant holding the sum of the killer cage at an index, whether or not the sum is written. It
+/// takes the prefix of the grid's squares so linked grids each get their own.
+pub fn cage_sum<'ctx>(sudoku: &Sudoku, index: usize, ctx: &'ctx Context) -> Int<'ctx> {
+    Int::new_const(ctx, format!("{}cageSum{index}", sudoku.prefix))
+}
+
+/// What the clue of the killer cage at an index gives: its sum, shifted by the shared offset and reduced by the
+/// shared modulus when the sudoku has them
+pub fn cage_clue_ast<'ctx>(sudoku: &Sudoku, index: usize, ctx: &'ctx Context) -> Int<'ctx> {
+    let mut total = cage_sum(sudoku, index, ctx);
+    if let Some(offset) = &sudoku.cage_sum_offset {
+        total = Int::add(ctx, &[&total, &offset.ast(ctx)]);
+    }
+    if let Some(modulo) = sudoku.cage_sum_modulo {
+        total = total.modulo(&Int::from_u64(ctx, modulo));
+    }
+    total
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountRelation {
+    Exactly,
+    AtLeast,
+    AtMost,
+}
+
+impl CountRelation {
+    pub fn name(self) -> &'static str {
+        match self {
+            CountRelation::Exactly => "exactly",
+            CountRelation::AtLeast => "atLeast",
+            CountRelation::AtMost => "atMost",
+        }
+    }
+}
+
+/// How many times a digit appears among some squares
+#[derive(Debug, Clone)]
+pub struct DigitCount {
+    pub cells: Vec<Vec<usize>>,
+    pub digit: u64,
+    pub relation: CountRelation,
+    pub count: Clue,
+}
+
+/// A rule applied to every square of a shaded region
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskRule {
+    Odd,
+    Even,
+    AtLeast(u64),
+    AtMost(u64),
+    Distinct,
+    Sum(Clue),
+}
+
+impl MaskRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaskRule::Odd => "odd",
+            MaskRule::Even => "even",
+            MaskRule::AtLeast(_) => "atLeast",
+            MaskRule::AtMost(_) => "atMost",
+            MaskRule::Distinct => "distinct",
+            MaskRule::Sum(_) => "sum",
+        }
+    }
+
+    /// Whether a digit may go in a square of the region regardless of the other squares
+    pub fn allows(&self, digit: u64) -> bool {
+        match self {
+            MaskRule::Odd => digit % 2 == 1,
+            MaskRule::Even => digit.is_multiple_of(2),
+            MaskRule::AtLeast(value) => digit >= *value,
+            MaskRule::AtMost(value) => digit <= *value,
+            MaskRule::Distinct | MaskRule::Sum(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Mask {
+    pub cells: Vec<Vec<usize>>,
+    pub rule: MaskRule,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Adjacency {
+    /// Squares share a side
+    Orthogonal,
+
+    /// Squares share a side or a corner
+    Diagonal,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Heatmap {
+    /// Coloured squares in the terminal
+    Ansi,
+
+    /// An SVG image
+    Svg,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Style {
+    /// Box drawing characters
+    Unicode,
+
+    /// Only ASCII characters, for terminals and log files that mangle Unicode
+    Ascii,
+
+    /// No borders, boxes set apart by spaces and blank lines
+    Minimal,
+}
+
+/// Style of the printed grids, set once from the arguments so every printer can read it
+pub static STYLE: OnceLock<Style> = OnceLock::new();
+
+/// The characters framing a grid in one style: the left end, fill, box joint and right end of the top rule,
+/// of the rules between bands and of the bottom rule, then the outer and inner sides of a row and the dashes
+/// drawn across and down along the edges of killer cages
+pub struct Borders {
+    pub top: [&'static str; 4],
+    pub middle: [&'static str; 4],
+    pub bottom: [&'static str; 4],
+    pub outer: &'static str,
+    pub inner: &'static str,
+    pub cage_across: &'static str,
+    pub cage_down: &'static str,
+}
+
+impl Style {
+    pub fn borders(self) -> Borders {
+        match self {
+            Style::Unicode => Borders {
+                top: ["╔", "═", "╤", "╗"],
+                middle: ["╟", "─", "┼", "╢"],
+                bottom: ["╚", "═", "╧", "╝"],
+                outer: "║",
+                inner: "│",
+                cage_across: "┄",
+                cage_down: "┆",
+            },
+            Style::Ascii => Borders {
+                top: ["+", "=", "+", "+"],
+                middle: ["+", "-", "+", "+"],
+                bottom: ["+", "=", "+", "+"],
+                outer: "|",
+                inner: "|",
+                cage_across: ".",
+                cage_down: ":",
+            },
+            Style::Minimal => Borders { top: [""; 4], middle: [""; 4], bottom: [""; 4], outer: "", inner: "", cage_across: "", cage_down: "" },
+        }
+    }
+}
+
+fn parse_offset_relation(group: &Value) -> OffsetRelation {
+    match group["relation"].as_str() {
+        None | Some("notEqual") => OffsetRelation::NotEqual,
+        Some("notConsecutive") => OffsetRelation::NotConsecutive,
+        Some("atLeast") => OffsetRelation::AtLeast(group["difference"].as_u64().unwrap()),
+        Some(relation) => panic!("Unknown offset relation {relation}"),
+    }
+}
+
+/// Parses a square written as rXcY, counting rows and columns from 0
+pub fn parse_cell(cell: &str, size: usize) -> Option<(usize, usize)> {
+    let (row, col) = cell.strip_prefix('r')?.split_once('c')?;
+    let (row, col) = (row.parse::<usize>().ok()?, col.parse::<usize>().ok()?);
+    if row < size && col < size {
+        Some((row, col))
+    } else {
+        None
+    }
+}
+
+/// Parses a restriction written as rXcY=2,4,6 into a digit region of that one square
+pub fn parse_restriction(restriction: &str, size: usize) -> Option<DigitRegion> {
+    let (cell, digits) = restriction.split_once('=')?;
+    let (row, col) = parse_cell(cell.trim(), size)?;
+    let digits = digits.split(',').map(|digit| digit.trim().parse().ok()).collect::<Option<Vec<u64>>>()?;
+    Some(DigitRegion { cells: vec![vec![row, col]], digits })
+}
+
+fn parse_or_default<T: serde::de::DeserializeOwned + Default>(v: &Value, key: &str) -> T {
+    if v[key].is_null() {
+        T::default()
+    } else {
+        serde_json::from_value(v[key].clone()).unwrap()
+    }
+}
+
+fn parse_soft_constraint(soft: &Value) -> SoftConstraint {
+    let rule = match soft["rule"].as_str() {
+        Some("given") => SoftRule::Given(soft["value"].as_u64().unwrap()),
+        Some("thermo") => SoftRule::Thermo,
+        Some("arrow") => SoftRule::Arrow,
+        Some("germanWhisper") => SoftRule::GermanWhisper,
+        Some("kropkiAdjacent") => SoftRule::KropkiAdjacent,
+        Some("kropkiDouble") => SoftRule::KropkiDouble,
+        Some("kropkiAmbiguous") => SoftRule::KropkiAmbiguous,
+        _ => panic!("Unknown soft rule {}", soft["rule"]),
+    };
+    SoftConstraint {
+        rule,
+        cells: serde_json::from_value(soft["cells"].clone()).unwrap(),
+        weight: soft["weight"].as_u64().unwrap_or(1),
+    }
+}
+
+fn parse_digit_count(count: &Value) -> DigitCount {
+    DigitCount {
+        cells: serde_json::from_value(count["cells"].clone()).unwrap(),
+        digit: count["digit"].as_u64().unwrap_or_else(|| panic!("Invalid digit count digit {}", count["digit"])),
+        relation: match count["relation"].as_str() {
+            None | Some("exactly") => CountRelation::Exactly,
+            Some("atLeast") => CountRelation::AtLeast,
+            Some("atMost") => CountRelation::AtMost,
+            Some(relation) => panic!("Unknown count relation {relation}"),
+        },
+        count: parse_clue(&count["count"]),
+    }
+}
+
+/// Masks list their squares in `cells` or shade them in a `mask` grid of booleans or 0s and 1s
+fn parse_mask(mask: &Value) -> Mask {
+    let rule = match mask["rule"].as_str() {
+        Some("odd") => MaskRule::Odd,
+        Some("even") => MaskRule::Even,
+        Some("atLeast") => MaskRule::AtLeast(mask["value"].as_u64().unwrap()),
+        Some("atMost") => MaskRule::AtMost(mask["value"].as_u64().unwrap()),
+        Some("distinct") => MaskRule::Distinct,
+        Some("sum") => MaskRule::Sum(parse_clue(&mask["value"])),
+        _ => panic!("Unknown mask rule {}", mask["rule"]),
+    };
+    let cells = match mask["mask"].as_array() {
+        Some(rows) => rows.iter().enumerate().flat_map(|(i, row)| {
+            row.as_array().unwrap().iter().enumerate()
+                .filter(|(_, shaded)| shaded.as_bool().unwrap_or_else(|| shaded.as_u64() == Some(1)))
+                .map(move |(j, _)| vec![i, j])
+        }).collect(),
+        None => serde_json::from_value(mask["cells"].clone()).unwrap(),
+    };
+    Mask { cells, rule }
+}
+
+/// Arrows are either a list of squares or an object with `cells`, the length of the `pill`, and a `distinct` flag for the shaft
+fn parse_arrow(arrow: &Value) -> Arrow {
+    if arrow.is_array() {
+        Arrow { cells: serde_json::from_value(arrow.clone()).unwrap(), pill: 1, distinct: false }
+    } else {
+        Arrow {
+            cells: serde_json::from_value(arrow["cells"].clone()).unwrap(),
+            pill: arrow["pill"].as_u64().unwrap_or(1) as usize,
+            distinct: arrow["distinct"].as_bool().unwrap_or(false),
+        }
+    }
+}
+
+fn parse_clue(value: &Value) -> Clue {
+    match value.as_str() {
+        Some(name) => Clue::Unknown(name.to_string()),
+        None => Clue::Known(value.as_u64().unwrap_or_else(|| panic!("Invalid clue {value}"))),
+    }
+}
+
+pub fn parse_side(value: &Value) -> Side {
+    match value.as_str() {
+        Some("left") => Side::Left,
+        Some("right") => Side::Right,
+        Some("top") => Side::Top,
+        Some("bottom") => Side::Bottom,
+        _ => panic!("Unknown side {value}"),
+    }
+}
+
+fn resolve_cells(cells: &Value, ids: &HashMap<&str, &Value>, seen: &mut Vec<String>) -> Vec<Vec<usize>> {
+    let mut resolved = Vec::new();
+    for cell in cells.as_array().unwrap() {
+        match cell.as_str() {
+            Some(id) => {
+                if seen.iter().any(|s| s == id) {
+                    panic!("Reference cycle through {id}");
+                }
+                let target = ids.get(id).unwrap_or_else(|| panic!("Unknown reference {id}"));
+                seen.push(id.to_string());
+                resolved.extend(resolve_cells(&target["cells"], ids, seen));
+                seen.pop();
+            }
+            None => resolved.push(serde_json::from_value(cell.clone()).unwrap()),
+        }
+    }
+    resolved
+}
+
+fn add_composite_lines(sudoku: &mut Sudoku, v: &Value) {
+    let lines = v["lines"].as_array().cloned().unwrap_or_default();
+    let ids = lines.iter().filter_map(|line| Some((line["id"].as_str()?, line))).collect::<HashMap<_, _>>();
+    for line in &lines {
+        let cells = resolve_cells(&line["cells"], &ids, &mut Vec::new());
+        for rule in line["rules"].as_array().into_iter().flatten() {
+            match rule.as_str() {
+                Some("thermo") => sudoku.thermo.push(cells.clone()),
+                Some("arrow") => sudoku.arrow.push(Arrow {
+                    cells: cells.clone(),
+                    pill: line["pill"].as_u64().unwrap_or(1) as usize,
+                    distinct: line["distinct"].as_bool().unwrap_or(false),
+                }),
+                Some("germanWhisper") => sudoku.german_whispers.push(cells.clone()),
+                Some("renban") => sudoku.renban.push(cells.clone()),
+                Some("lockoutLine") => sudoku.lockout_lines.push(cells.clone()),
+                Some("entropicLine") => sudoku.entropic_lines.push(cells.clone()),
+                Some("modularLine") => sudoku.modular_lines.push(cells.clone()),
+                Some("regionSumLine") => sudoku.region_sum_lines.push(cells.clone()),
+                Some("kropkiAdjacent") => sudoku.kropki_adjacent.push(cells.clone()),
+                Some("kropkiDouble") => sudoku.kropki_double.push(cells.clone()),
+                Some("kropkiAmbiguous") => sudoku.kropki_ambiguous.push(cells.clone()),
+                _ => panic!("Unknown line rule {rule}"),
+            }
+        }
+    }
+}
+
+/// Replaces every `{"template": name, "at": [row, col]}` with the squares of the named template shifted by the anchor
+fn expand_templates(v: &Value, templates: &Value) -> Value {
+    match v {
+        Value::Object(object) if object.contains_key("template") => {
+            let name = object["template"].as_str().unwrap_or_else(|| panic!("Invalid template {}", object["template"]));
+            if templates[name].is_null() {
+                panic!("Unknown template {name}");
+            }
+            let cells: Vec<[i64; 2]> = serde_json::from_value(templates[name].clone()).unwrap();
+            let [row, col]: [i64; 2] = if object["at"].is_null() { [0, 0] } else { serde_json::from_value(object["at"].clone()).unwrap() };
+            let shifted = cells.iter().map(|[i, j]| {
+                let cell = [usize::try_from(i + row), usize::try_from(j + col)];
+                match cell {
+                    [Ok(i), Ok(j)] => vec![i, j],
+                    _ => panic!("Template {name} at [{row}, {col}] leaves the grid"),
+                }
+            }).collect::<Vec<_>>();
+            serde_json::to_value(shifted).unwrap()
+        }
+        Value::Object(object) => Value::Object(object.iter().map(|(key, value)| (key.clone(), expand_templates(value, templates))).collect()),
+        Value::Array(values) => Value::Array(values.iter().map(|value| expand_templates(value, templates)).collect()),
+        _ => v.clone(),
+    }
+}
+
+/// Lists whose entries can carry a `mirror` or `rotate`, or take the one of the whole puzzle
+pub const SYMMETRIC_ELEMENTS: [&str; 16] = [
+    "thermo", "arrow", "kropkiAdjacent", "kropkiDouble", "kropkiAmbiguous", "germanWhispers", "renban", "lockoutLines", "entropicLines",
+    "modularLines", "regionSumLines", "consecutiveBars", "lines", "digitRegions", "killerCages", "digitCounts",
+];
+
+/// The squares of an element followed by those of its distinct copies under the `mirror` and `rotate` of the transform
+fn symmetric_cells(cells: &[[usize; 2]], transform: &Value, size: usize) -> Vec<Vec<[usize; 2]>> {
+    if let Some(cell) = cells.iter().find(|cell| cell[0] >= size || cell[1] >= size) {
+        panic!("Cannot mirror or rotate square {cell:?} outside the grid");
+    }
+    let mirror = |[i, j]: [usize; 2]| match transform["mirror"].as_str() {
+        Some("horizontal") => [i, size - 1 - j],
+        Some("vertical") => [size - 1 - i, j],
+        _ => panic!("Unknown mirror {}", transform["mirror"]),
+    };
+    let turns = match transform["rotate"].as_u64() {
+        None => 1,
+        Some(180) => 2,
+        Some(90 | 270) => 4,
+        Some(angle) => panic!("Cannot rotate by {angle} degrees"),
+    };
+    let mut copies: Vec<Vec<[usize; 2]>> = Vec::new();
+    let mut current = cells.to_vec();
+    for _ in 0..turns {
+        let mut images = vec![current.clone()];
+        if !transform["mirror"].is_null() {
+            images.push(current.iter().map(|&cell| mirror(cell)).collect());
+        }
+        for image in images {
+            if !copies.contains(&image) {
+                copies.push(image);
+            }
+        }
+        for _ in 0..4 / turns {
+            current = current.iter().map(|&[i, j]| [j, size - 1 - i]).collect();
+        }
+    }
+    copies
+}
+
+/// Replaces every element that is mirrored or rotated, by itself or through the whole puzzle, with all of its copies
+fn expand_symmetry(v: &mut Value) {
+    let size = v["given"].as_array().map_or(0, |rows| rows.len());
+    let whole = serde_json::json!({"mirror": v["mirror"], "rotate": v["rotate"]});
+    for key in SYMMETRIC_ELEMENTS {
+        let Some(entries) = v[key].as_array() else {
+            continue;
+        };
+        let mut expanded = Vec::new();
+        for entry in entries {
+            let transform = if entry["mirror"].is_null() && entry["rotate"].is_null() { &whole } else { entry };
+            if transform["mirror"].is_null() && transform["rotate"].is_null() {
+                expanded.push(entry.clone());
+                continue;
+            }
+            let cells = if entry.is_object() { &entry["cells"] } else { entry };
+            let cells: Vec<[usize; 2]> = serde_json::from_value(cells.clone())
+                .unwrap_or_else(|_| panic!("Cannot mirror or rotate {cells}, which is not a list of squares"));
+            for (n, copy) in symmetric_cells(&cells, transform, size).into_iter().enumerate() {
+                let mut object = entry.as_object().cloned().unwrap_or_default();
+                object.remove("mirror");
+                object.remove("rotate");
+                if n > 0 {
+                    object.remove("id");
+                }
+                object.insert("cells".to_string(), serde_json::to_value(copy).unwrap());
+                if object.len() == 1 {
+                    expanded.push(object.remove("cells").unwrap());
+                } else {
+                    expanded.push(Value::Object(object));
+                }
+            }
+        }
+        v[key] = Value::Array(expanded);
+    }
+}
+
+/// Overlays an included sudoku, adding its elements and givens while keeping the settings already present
+fn merge_sudoku(v: &mut Value, other: &Value) {
+    let digits = if v["digits"].is_null() && v["digitSet"].is_null() { parse_digits(other) } else { parse_digits(v) };
+    let blank = |given: &Value| given.as_u64().is_none_or(|given| !digits.contains(&given));
+    for (key, value) in other.as_object().unwrap() {
+        if key == "includes" {
+            continue;
+        }
+        if v[key].is_null() {
+            v[key] = value.clone();
+            continue;
+        }
+        match (key.as_str(), v.get_mut(key).unwrap(), value) {
+            ("given", Value::Array(rows), Value::Array(others)) => {
+                if rows.len() != others.len() {
+                    panic!("Cannot include a {0}x{0} grid in a {1}x{1} one", others.len(), rows.len());
+                }
+                for (i, (row, other_row)) in rows.iter_mut().zip(others).enumerate() {
+                    for (j, (given, other_given)) in row.as_array_mut().unwrap().iter_mut().zip(other_row.as_array().unwrap()).enumerate() {
+                        if blank(given) {
+                            *given = other_given.clone();
+                        } else if !blank(other_given) && given != other_given {
+                            panic!("Conflicting givens at r{i}c{j}: {given} and {other_given}");
+                        }
+                    }
+                }
+            }
+            ("digits" | "digitSet", _, _) => {}
+            (_, Value::Array(values), Value::Array(others)) => values.extend(others.iter().cloned()),
+            (_, Value::Object(values), Value::Object(others)) => {
+                for (name, other) in others {
+                    values.entry(name.clone()).or_insert_with(|| other.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads a sudoku and the files listed in its `includes`, which are relative to it and may include others in turn
+fn load_sudoku_json(fp: &Path, seen: &mut Vec<PathBuf>) -> Value {
+    let path = fp.canonicalize().unwrap_or_else(|_| panic!("Cannot open {}", fp.display()));
+    if seen.contains(&path) {
+        panic!("Include cycle through {}", path.display());
+    }
+    let file = File::open(&path).unwrap();
+    let reader = BufReader::new(file);
+    let mut v: Value = serde_json::from_reader(reader).unwrap();
+    seen.push(path.clone());
+    for include in v["includes"].as_array().cloned().unwrap_or_default() {
+        let include = include.as_str().unwrap_or_else(|| panic!("Invalid include {include}"));
+        let included = load_sudoku_json(&path.parent().unwrap().join(include), seen);
+        merge_sudoku(&mut v, &included);
+    }
+    seen.pop();
+    if let Some(object) = v.as_object_mut() {
+        object.remove("includes");
+    }
+    v
+}
+
+pub fn open_sudoku(fp: &String) -> Sudoku {
+    let v = load_sudoku_json(Path::new(fp), &mut Vec::new());
+    parse_sudoku(&v)
+}
+
+fn parse_digits(v: &Value) -> Vec<u64> {
+    let mut digits: Vec<u64> = if !v["digitSet"].is_null() {
+        serde_json::from_value(v["digitSet"].clone()).unwrap()
+    } else {
+        let [low, high]: [u64; 2] = if v["digits"].is_null() { [1, 9] } else { serde_json::from_value(v["digits"].clone()).unwrap() };
+        (low..=high).collect()
+    };
+    digits.sort();
+    digits.dedup();
+    digits
+}
+
+fn parse_state(v: &Value, digits: &[u64], size: usize) -> State {
+    let mut filled = vec![vec![None; size]; size];
+    for (i, row) in v["filled"].as_array().into_iter().flatten().enumerate() {
+        for (j, digit) in row.as_array().unwrap().iter().enumerate() {
+            filled[i][j] = digit.as_u64().filter(|digit| digits.contains(digit));
+        }
+    }
+    let pencilmarks = v["pencilmarks"].as_array().map(|marks| marks.iter().map(|mark| Pencilmark {
+        cell: serde_json::from_value(mark["cell"].clone()).unwrap(),
+        digits: serde_json::from_value(mark["digits"].clone()).unwrap(),
+    }).collect()).unwrap_or_default();
+    State { filled, pencilmarks }
+}
+
+/// Turns every clue written as "?" into an unknown named after the clue, so it is solved for and printed with the solution
+fn name_hidden_clues(sudoku: &mut Sudoku) {
+    let bound = sudoku.size() as i64 * sudoku.digits.iter().sum::<u64>() as i64;
+    let mut hidden = Vec::new();
+    let mut name = |clue: &mut Clue, label: String| {
+        if *clue == Clue::Unknown("?".to_string()) {
+            *clue = Clue::Unknown(label.clone());
+            hidden.push(Unknown { name: label, min: 0, max: bound });
+        }
+    };
+    for sandwich in &mut sudoku.sandwich {
+        name(&mut sandwich.sum, format!("sandwich {} {}", sandwich.side.name(), sandwich.index));
+    }
+    for frame in &mut sudoku.frame {
+        name(&mut frame.sum, format!("frame {} {}", frame.side.name(), frame.index));
+    }
+    for skyscraper in &mut sudoku.skyscraper {
+        name(&mut skyscraper.count, format!("skyscraper {} {}", skyscraper.side.name(), skyscraper.index));
+    }
+    for x_sum in &mut sudoku.x_sum {
+        name(&mut x_sum.sum, format!("x-sum {} {}", x_sum.side.name(), x_sum.index));
+    }
+    name(&mut sudoku.whisper_difference, "whisper difference".to_string());
+    for (i, cage) in sudoku.killer_cages.iter_mut().enumerate() {
+        if let Some(sum) = &mut cage.sum {
+            name(sum, format!("cage {i}"));
+        }
+    }
+    if let Some(offset) = &mut sudoku.cage_sum_offset {
+        name(offset, "cage offset".to_string());
+    }
+    for (i, mask) in sudoku.masks.iter_mut().enumerate() {
+        if let MaskRule::Sum(sum) = &mut mask.rule {
+            name(sum, format!("mask {i}"));
+        }
+    }
+    for (i, count) in sudoku.digit_counts.iter_mut().enumerate() {
+        name(&mut count.count, format!("digit count {i}"));
+    }
+    sudoku.unknowns.extend(hidden);
+}
+
+/// Offsets of the squares sharing a side with a square
+pub const ORTHOGONAL: [[i32; 2]; 4] = [[-1, 0], [0, -1], [0, 1], [1, 0]];
+
+/// Replaces every name in `offsetPresets` with its offsets, added to `offsets` for presets forbidding equal digits and
+/// as a named offset group otherwise
+fn expand_offset_presets(v: &mut Value) {
+    const KNIGHT: [[i32; 2]; 8] = [[-2, -1], [-2, 1], [-1, -2], [-1, 2], [1, -2], [1, 2], [2, -1], [2, 1]];
+    const KING: [[i32; 2]; 8] = [[-1, -1], [-1, 0], [-1, 1], [0, -1], [0, 1], [1, -1], [1, 0], [1, 1]];
+    let Some(object) = v.as_object_mut() else {
+        return;
+    };
+    let Some(presets) = object.remove("offsetPresets") else {
+        return;
+    };
+    let mut offsets: Vec<[i32; 2]> = serde_json::from_value(object.get("offsets").cloned().unwrap_or(json!([]))).unwrap();
+    for preset in presets.as_array().unwrap_or_else(|| panic!("Offset presets must be a list, not {presets}")) {
+        match preset.as_str() {
+            Some("anti-knight") => offsets.extend(KNIGHT),
+            Some("anti-king") => offsets.extend(KING),
+            Some("anti-consecutive-orthogonal") => {
+                let groups = object.entry("offsetGroups").or_insert(json!([]));
+                groups.as_array_mut().unwrap().push(json!({
+                    "name": "anti-consecutive-orthogonal",
+                    "offsets": ORTHOGONAL,
+                    "relation": "notConsecutive",
+                }));
+            }
+            _ => panic!("Unknown offset preset {preset}, use anti-knight, anti-king or anti-consecutive-orthogonal"),
+        }
+    }
+    offsets.sort();
+    offsets.dedup();
+    object.insert("offsets".to_string(), json!(offsets));
+}
+
+/// Turns `boxCages`, one sum or null for each box in reading order, into killer cages covering the boxes
+fn expand_box_cages(v: &mut Value) {
+    let size = v["given"].as_array().map_or(0, |rows| rows.len());
+    let Some(object) = v.as_object_mut() else {
+        return;
+    };
+    let Some(sums) = object.remove("boxCages") else {
+        return;
+    };
+    let sums = sums.as_array().filter(|sums| sums.len() == size)
+        .unwrap_or_else(|| panic!("Box cages must list a sum or null for each of the {size} boxes, not {sums}"));
+    let cages = object.entry("killerCages").or_insert(json!([]));
+    for (squares, sum) in nonets(size).into_iter().zip(sums) {
+        let mut cage = json!({"cells": squares.iter().map(|&(i, j)| [i, j]).collect::<Vec<_>>()});
+        if !sum.is_null() {
+            cage["sum"] = sum.clone();
+        }
+        cages.as_array_mut().unwrap().push(cage);
+    }
+}
+
+fn parse_sudoku(v: &Value) -> Sudoku {
+    let mut expanded = expand_templates(v, &v["templates"]);
+    expand_symmetry(&mut expanded);
+    expand_offset_presets(&mut expanded);
+    expand_box_cages(&mut expanded);
+    let v = &expanded;
+    let digits = parse_digits(v);
+    let mut sudoku = Sudoku {
+        given: v["given"].as_array().unwrap().iter().map(|row| row.as_array().unwrap().iter().map(
+            |given| given.as_u64().filter(|given| digits.contains(given))
+        ).collect()).collect(),
+        unknown_givens: parse_or_default(v, "unknownGivens"),
+        fog: parse_or_default(v, "fog"),
+        digit_regions: v["digitRegions"].as_array().map(|regions| regions.iter().map(|region| DigitRegion {
+            cells: serde_json::from_value(region["cells"].clone()).unwrap(),
+            digits: serde_json::from_value(region["digits"].clone()).unwrap(),
+        }).collect()).unwrap_or_default(),
+        horizontal_rule: serde_json::from_value(v["1-9horiz"].clone()).unwrap(),
+        vertical_rule: serde_json::from_value(v["1-9vert"].clone()).unwrap(),
+        nonet_rule: serde_json::from_value(v["1-9nonet"].clone()).unwrap(),
+        windoku: v["windoku"].as_bool().unwrap_or(false),
+        disjoint_groups: v["disjointGroups"].as_bool().unwrap_or(false),
+        positive_diagonal: v["diagPos"].as_bool().unwrap_or(false),
+        negative_diagonal: v["diagNeg"].as_bool().unwrap_or(false),
+        offset: serde_json::from_value(v["offsets"].clone()).unwrap(),
+        offset_groups: v["offsetGroups"].as_array().map(|groups| groups.iter().map(|group| OffsetGroup {
+            name: serde_json::from_value(group["name"].clone()).unwrap(),
+            offsets: serde_json::from_value(group["offsets"].clone()).unwrap(),
+            relation: parse_offset_relation(group),
+        }).collect()).unwrap_or_default(),
+        taxicab: v["taxicab"].as_bool().unwrap_or(false),
+        non_consecutive: v["nonConsecutive"].as_bool().unwrap_or(false),
+        odd_shading: parse_or_default(v, "oddShading"),
+        odd_cells: parse_or_default(v, "oddCells"),
+        even_cells: parse_or_default(v, "evenCells"),
+        quadruples: v["quadruples"].as_array().map(|quadruples| quadruples.iter().map(|quadruple| Quadruple {
+            cell: serde_json::from_value(quadruple["cell"].clone()).unwrap(),
+            digits: serde_json::from_value(quadruple["digits"].clone()).unwrap(),
+        }).collect()).unwrap_or_default(),
+        thermo: serde_json::from_value(v["thermo"].clone()).unwrap(),
+        arrow: v["arrow"].as_array().unwrap().iter().map(parse_arrow).collect(),
+        kropki_adjacent: serde_json::from_value(v["kropkiAdjacent"].clone()).unwrap(),
+        kropki_double: serde_json::from_value(v["kropkiDouble"].clone()).unwrap(),
+        kropki_ambiguous: parse_or_default(v, "kropkiAmbiguous"),
+        consecutive_bars: parse_or_default(v, "consecutiveBars"),
+        german_whispers: serde_json::from_value(v["germanWhispers"].clone()).unwrap(),
+        renban: parse_or_default(v, "renban"),
+        lockout_lines: parse_or_default(v, "lockoutLines"),
+        entropic_lines: parse_or_default(v, "entropicLines"),
+        modular_lines: parse_or_default(v, "modularLines"),
+        region_sum_lines: parse_or_default(v, "regionSumLines"),
+        whisper_difference: if v["whisperDifference"].is_null() { Clue::Known(5) } else { parse_clue(&v["whisperDifference"]) },
+        row_indexing: parse_or_default(v, "rowIndexing"),
+        column_indexing: parse_or_default(v, "columnIndexing"),
+        sandwich: v["sandwich"].as_array().map(|clues| clues.iter().map(|clue| Sandwich {
+            side: parse_side(&clue["side"]),
+            index: serde_json::from_value(clue["index"].clone()).unwrap(),
+            sum: parse_clue(&clue["sum"]),
+            crusts: if clue["crusts"].is_null() { [digits[0], digits[digits.len() - 1]] } else { serde_json::from_value(clue["crusts"].clone()).unwrap() },
+        }).collect()).unwrap_or_default(),
+        frame: v["frame"].as_array().map(|clues| clues.iter().map(|clue| Frame {
+            side: parse_side(&clue["side"]),
+            index: serde_json::from_value(clue["index"].clone()).unwrap(),
+            sum: parse_clue(&clue["sum"]),
+        }).collect()).unwrap_or_default(),
+        skyscraper: v["skyscraper"].as_array().map(|clues| clues.iter().map(|clue| Skyscraper {
+            side: parse_side(&clue["side"]),
+            index: serde_json::from_value(clue["index"].clone()).unwrap(),
+            count: parse_clue(&clue["count"]),
+        }).collect()).unwrap_or_default(),
+        x_sum: v["xSum"].as_array().map(|clues| clues.iter().map(|clue| XSum {
+            side: parse_side(&clue["side"]),
+            index: serde_json::from_value(clue["index"].clone()).unwrap(),
+            sum: parse_clue(&clue["sum"]),
+        }).collect()).unwrap_or_default(),
+        unknowns: v["unknowns"].as_array().map(|unknowns| unknowns.iter().map(|unknown| Unknown {
+            name: serde_json::from_value(unknown["name"].clone()).unwrap(),
+            min: serde_json::from_value(unknown["min"].clone()).unwrap(),
+            max: serde_json::from_value(unknown["max"].clone()).unwrap(),
+        }).collect()).unwrap_or_default(),
+        soft: v["soft"].as_array().map(|soft| soft.iter().map(parse_soft_constraint).collect()).unwrap_or_default(),
+        masks: v["masks"].as_array().map(|masks| masks.iter().map(parse_mask).collect()).unwrap_or_default(),
+        line_order: v["lineOrder"].as_array().map(|orders| orders.iter().map(|order| LineOrder {
+            lesser: Reading { side: parse_side(&order["lesser"]["side"]), index: serde_json::from_value(order["lesser"]["index"].clone()).unwrap() },
+            greater: Reading { side: parse_side(&order["greater"]["side"]), index: serde_json::from_value(order["greater"]["index"].clone()).unwrap() },
+        }).collect()).unwrap_or_default(),
+        digit_counts: v["digitCounts"].as_array().map(|counts| counts.iter().map(parse_digit_count).collect()).unwrap_or_default(),
+        distinct_cage_sums: v["distinctCageSums"].as_bool().unwrap_or(false),
+        cage_anti_adjacency: v["cageAntiAdjacency"].as_bool().unwrap_or(false),
+        cage_sum_modulo: v["cageSumModulo"].as_u64(),
+        cage_sum_offset: if v["cageSumOffset"].is_null() { None } else { Some(parse_clue(&v["cageSumOffset"])) },
+        killer_cages: v["killerCages"].as_array().map(|cages| cages.iter().map(|cage| Cage {
+            cells: serde_json::from_value(cage["cells"].clone()).unwrap(),
+            sum: if cage["sum"].is_null() { None } else { Some(parse_clue(&cage["sum"])) },
+            relation: match cage["relation"].as_str() {
+                None | Some("equal") => CageRelation::Equal,
+                Some("lessThan") => CageRelation::LessThan,
+                Some("greaterThan") => CageRelation::GreaterThan,
+                Some(relation) => panic!("Unknown cage relation {relation}"),
+            },
+        }).collect()).unwrap_or_default(),
+        state: parse_state(&v["state"], &digits, digits.len()),
+        prefix: String::new(),
+        prune: false,
+        digits,
+    };
+    add_composite_lines(&mut sudoku, v);
+    if !sudoku.entropic_lines.is_empty() && (sudoku.digits.is_empty() || !sudoku.digits.len().is_multiple_of(3)) {
+        panic!("Entropic lines need the {} digits to split into three equal groups", sudoku.digits.len());
+    }
+    if sudoku.cage_sum_modulo == Some(0) {
+        panic!("Cage sums cannot be taken modulo 0");
+    }
+    name_hidden_clues(&mut sudoku);
+    sudoku
+}
+
+/// A classic sudoku from its rows of givens, with `.` for an empty square
+#[cfg(test)]
+pub(crate) fn classic_sudoku(rows: &[&str]) -> Sudoku {
+    let given = rows.iter().map(|row| row.chars().map(|c| c.to_digit(10).unwrap_or(0)).collect::<Vec<_>>()).collect::<Vec<_>>();
+    parse_sudoku(&json!({
+        "given": given, "thermo": [], "arrow": [], "kropkiAdjacent": [], "kropkiDouble": [], "germanWhispers": [],
+        "1-9horiz": true, "1-9vert": true, "1-9nonet": true, "offsets": [],
+    }))
+}
+
+pub fn new_grid(ctx: &Context, size: usize) -> Vec<Vec<Int<'_>>> {
+    new_named_grid(ctx, size, "")
+}
+
+/// A grid whose squares are named with a prefix, so several grids can live in one solver
+fn new_named_grid<'ctx>(ctx: &'ctx Context, size: usize, prefix: &str) -> Vec<Vec<Int<'ctx>>> {
+    (0..size).map(|i| (0..size).map(|j| Int::new_const(ctx, format!("{prefix}r{i}c{j}"))).collect()).collect()
+}
+
+pub fn solution_from_model(model: &Model, grid: &[Vec<Int<'_>>]) -> Vec<Vec<u64>> {
+    grid.iter().map(
+        |row| row.iter().map(|cell| model.get_const_interp(cell).unwrap().as_u64().unwrap()).collect()
+    ).collect()
+}
+
+/// Prints the solution in the model, with the killer cages of the sudoku drawn around it
+pub fn print_sudoku_from_model(sudoku: &Sudoku, model: &Model, grid: &[Vec<Int<'_>>]) {
+    print_solution(sudoku, &solution_from_model(model, grid));
+}
+
+/// Prints a solved grid, with the killer cages of the sudoku drawn around it
+pub fn print_solution(sudoku: &Sudoku, solution: &[Vec<u64>]) {
+    let cells = solution.iter().map(|row| row.iter().map(|digit| digit.to_string()).collect()).collect::<Vec<Vec<_>>>();
+    let width = cells.iter().flatten().map(|cell| cell.len()).max().unwrap_or(1);
+    let cells = cells.iter().map(|row| row.iter().map(|cell| format!("{cell:<width$}")).collect()).collect::<Vec<_>>();
+    let borders = STYLE.get().copied().unwrap_or(Style::Unicode).borders();
+    if sudoku.killer_cages.is_empty() || borders.cage_down.is_empty() {
+        print_boxed(&cells, width);
+    } else {
+        print_caged(&cells, width, &sudoku.killer_cages, &borders);
+    }
+}
+
+pub fn print_unknowns(sudoku: &Sudoku, model: &Model, ctx: &Context) {
+    for unknown in &sudoku.unknowns {
+        println!("{} = {}", unknown.name, model.eval(&Int::new_const(ctx, unknown.name.as_str()), true).unwrap());
+    }
+    for (i, cage) in sudoku.killer_cages.iter().enumerate() {
+        if cage.sum.is_none() {
+            println!("Killer cage {i} sums to {}", model.eval(&cage_sum(sudoku, i, ctx), true).unwrap());
+        }
+    }
+}
+
+/// Prints rows of cells, each already padded to the same visible width, split into boxes in the chosen style
+fn print_boxed(cells: &[Vec<String>], width: usize) {
+    let size = cells.len();
+    let (box_height, box_width) = box_shape(size);
+    let borders = STYLE.get().copied().unwrap_or(Style::Unicode).borders();
+    let rule = |[left, fill, joint, right]: [&str; 4]| {
+        format!("{left}{}{right}", vec![fill.repeat(box_width * (width + 1) + 1); size / box_width].join(joint))
+    };
+    if !rule(borders.top).is_empty() {
+        println!("{}", rule(borders.top));
+    }
+    for (i, row) in cells.iter().enumerate() {
+        let mut line = borders.outer.to_string();
+        for (j, cell) in row.iter().enumerate() {
+            line += &format!(" {cell}");
+            if j % box_width == box_width - 1 {
+                line += &format!(" {}", if j == size - 1 { borders.outer } else { borders.inner });
+            }
+        }
+        println!("{}", line.trim());
+        if i % box_height == box_height - 1 && i != size - 1 {
+            println!("{}", rule(borders.middle));
+        }
+    }
+    if !rule(borders.bottom).is_empty() {
+        println!("{}", rule(borders.bottom));
+    }
+}
+
+/// Prints rows of cells like print_boxed but with a column between every two squares and a rule between every
+/// two rows, dashed along the edges of the killer cages, with the sum of each cage over its first square
+fn print_caged(cells: &[Vec<String>], width: usize, cages: &[Cage], borders: &Borders) {
+    let size = cells.len();
+    let (box_height, box_width) = box_shape(size);
+    let mut cage_of = vec![vec![None; size]; size];
+    // One more row of labels for the bottom rule, which has none
+    let mut labels = vec![vec![String::new(); size]; size + 1];
+    for (index, cage) in cages.iter().enumerate() {
+        for cell in &cage.cells {
+            cage_of[cell[0]][cell[1]] = Some(index);
+        }
+        if let (Some(sum), Some(first)) = (&cage.sum, cage.cells.iter().min()) {
+            let sum = match sum {
+                Clue::Known(sum) => sum.to_string(),
+                Clue::Unknown(_) => "?".to_string(),
+            };
+            labels[first[0]][first[1]] = match cage.relation {
+                CageRelation::Equal => sum,
+                CageRelation::LessThan => format!("<{sum}"),
+                CageRelation::GreaterThan => format!(">{sum}"),
+            };
+        }
+    }
+    let split = |a: (usize, usize), b: (usize, usize)| cage_of[a.0][a.1] != cage_of[b.0][b.1];
+    let slot = |label: &str, fill: &str| format!("{label}{}", fill.repeat((width + 2).saturating_sub(label.chars().count())));
+    // The rule above row i, or below the last row when i is the size
+    let rule = |i: usize| {
+        let band = i.is_multiple_of(box_height);
+        let [left, fill, joint, right] = match i {
+            0 => borders.top,
+            _ if i == size => borders.bottom,
+            _ if band => borders.middle,
+            _ => [borders.outer, " ", borders.inner, borders.outer],
+        };
+        let mut line = left.to_string();
+        for (j, label) in labels[i].iter().enumerate() {
+            let edge = band || split((i - 1, j), (i, j));
+            line += &slot(label, if edge && !band { borders.cage_across } else { fill });
+            if j == size - 1 {
+                line += right;
+            } else if j % box_width == box_width - 1 {
+                line += joint;
+            } else if band {
+                line += fill;
+            } else if split((i - 1, j), (i - 1, j + 1)) || split((i, j), (i, j + 1)) {
+                line += borders.cage_down;
+            } else if edge || split((i - 1, j + 1), (i, j + 1)) {
+                line += borders.cage_across;
+            } else {
+                line += " ";
+            }
+        }
+        line
+    };
+    for (i, row) in cells.iter().enumerate() {
+        println!("{}", rule(i));
+        let mut line = borders.outer.to_string();
+        for (j, cell) in row.iter().enumerate() {
+            line += &format!(" {cell} ");
+            line += if j == size - 1 {
+                borders.outer
+            } else if j % box_width == box_width - 1 {
+                borders.inner
+            } else if split((i, j), (i, j + 1)) {
+                borders.cage_down
+            } else {
+                " "
+            };
+        }
+        println!("{line}");
+    }
+    println!("{}", rule(size));
+}
+
+pub fn print_candidate_grid(candidates: &[Vec<Vec<u64>>], highlight: &[(usize, usize)]) {
+    let cells = candidates.iter().map(|row| row.iter().map(
+        |digits| if digits.is_empty() { ".".to_string() } else { digits.iter().map(|d| d.to_string()).collect::<String>() }
+    ).collect::<Vec<_>>()).collect::<Vec<_>>();
+    let width = cells.iter().flatten().map(|cell| cell.len()).max().unwrap_or(1);
+    let colour = std::io::stdout().is_terminal();
+    let cells = cells.iter().enumerate().map(|(i, row)| row.iter().enumerate().map(|(j, cell)| {
+        if colour && highlight.contains(&(i, j)) {
+            format!("\x1b[7m{cell:<width$}\x1b[0m")
+        } else {
+            format!("{cell:<width$}")
+        }
+    }).collect()).collect::<Vec<_>>();
+    print_boxed(&cells, width);
+}
+
+pub fn block_solution(model: &Model, grid: &[Vec<Int<'_>>], solver: &Solver, ctx: &z3::Context) {
+    let a = grid.iter().flat_map(
+        |x| x.iter().map(
+            |y| Bool::not(&y._eq(&model.get_const_interp(y).unwrap()))
+        ).collect::<Vec<_>>()
+    ).collect::<Vec<_>>();
+    solver.assert(&Bool::or(ctx, &a.iter().collect::<Vec<_>>()[..]));
+}
+
+pub fn count_up_to(grid: &[Vec<Int<'_>>], solver: &Solver, ctx: &z3::Context, limit: u32) -> u32 {
+    solver.push();
+    let mut count = 0;
+    while count < limit {
+        match solver.check() {
+            SatResult::Sat => {
+                let model = solver.get_model().unwrap();
+                block_solution(&model, grid, solver, ctx);
+                count += 1;
+            }
+            SatResult::Unsat => break,
+            SatResult::Unknown if interrupted_query(solver.get_reason_unknown()) => break,
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        }
+    }
+    solver.pop(1);
+    count
+}
+
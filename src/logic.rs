@@ -0,0 +1,670 @@
+use crate::{disjoint_groups, nonets, windows, Sudoku};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    Pointing,
+    Claiming,
+    /// Squares of a unit holding between them only as many digits as there are squares
+    NakedSubset(usize),
+    /// Digits of a unit fitting between them in only as many squares as there are digits
+    HiddenSubset(usize),
+    /// A digit confined in as many rows as columns, or the other way around
+    Fish(usize),
+    /// A candidate that leads to a contradiction when assumed
+    Chain,
+}
+
+impl Technique {
+    pub fn name(self) -> &'static str {
+        match self {
+            Technique::NakedSingle => "Naked single",
+            Technique::HiddenSingle => "Hidden single",
+            Technique::Pointing => "Pointing",
+            Technique::Claiming => "Claiming",
+            Technique::NakedSubset(2) => "Naked pair",
+            Technique::NakedSubset(3) => "Naked triple",
+            Technique::NakedSubset(_) => "Naked quad",
+            Technique::HiddenSubset(2) => "Hidden pair",
+            Technique::HiddenSubset(3) => "Hidden triple",
+            Technique::HiddenSubset(_) => "Hidden quad",
+            Technique::Fish(2) => "X-wing",
+            Technique::Fish(3) => "Swordfish",
+            Technique::Fish(_) => "Jellyfish",
+            Technique::Chain => "Forcing chain",
+        }
+    }
+}
+
+/// Every technique short of chains, easiest first: singles, locked candidates, subsets of each size and then fish
+pub const TECHNIQUES: [Technique; 13] = [
+    Technique::NakedSingle,
+    Technique::HiddenSingle,
+    Technique::Pointing,
+    Technique::Claiming,
+    Technique::NakedSubset(2),
+    Technique::HiddenSubset(2),
+    Technique::NakedSubset(3),
+    Technique::HiddenSubset(3),
+    Technique::NakedSubset(4),
+    Technique::HiddenSubset(4),
+    Technique::Fish(2),
+    Technique::Fish(3),
+    Technique::Fish(4),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UnitKind {
+    Row,
+    Column,
+    Box,
+    Diagonal,
+    Group,
+}
+
+/// A row, column, box, window, diagonal or disjoint group whose squares hold every digit exactly once
+#[derive(Clone)]
+struct Unit {
+    name: String,
+    kind: UnitKind,
+    cells: Vec<(usize, usize)>,
+}
+
+/// Every way to choose k of the first n indices, in increasing order
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    (k - 1..n).flat_map(|last| combinations(last, k - 1).into_iter().map(move |mut combination| {
+        combination.push(last);
+        combination
+    })).collect()
+}
+
+/// One deduction, with the units it is based on and everything it removes
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub technique: Technique,
+    pub digits: Vec<u64>,
+    /// Squares the digits are confined to
+    pub cells: Vec<(usize, usize)>,
+    /// Units the digits are confined to
+    pub base: Vec<String>,
+    /// Units the digits are eliminated from
+    pub cover: Vec<String>,
+    pub placement: Option<(usize, usize, u64)>,
+    pub eliminations: Vec<(usize, usize, u64)>,
+}
+
+fn cells(cells: &[(usize, usize)]) -> String {
+    cells.iter().map(|(i, j)| format!("r{i}c{j}")).collect::<Vec<_>>().join(", ")
+}
+
+fn digits(digits: &[u64]) -> String {
+    digits.iter().map(|digit| digit.to_string()).collect::<Vec<_>>().join("")
+}
+
+impl Step {
+    pub fn explain(&self) -> String {
+        let mut eliminated = self.eliminations.iter().map(|&(i, j, _)| (i, j)).collect::<Vec<_>>();
+        eliminated.dedup();
+        match (self.technique, self.placement) {
+            (Technique::NakedSingle, Some((i, j, digit))) => format!("Naked single: r{i}c{j} can only be {digit}"),
+            (Technique::HiddenSingle, Some((i, j, digit))) => {
+                format!("Hidden single: {digit} can only go in r{i}c{j} within {}", self.base.join(", "))
+            }
+            (Technique::Chain, _) => {
+                let (i, j) = self.cells[0];
+                format!("Forcing chain: r{i}c{j} cannot be {}", digits(&self.digits))
+            }
+            (Technique::NakedSubset(_), _) => format!(
+                "{}: {} only hold {} in {}, eliminating them from {}",
+                self.technique.name(), cells(&self.cells), digits(&self.digits), self.base.join(", "), cells(&eliminated),
+            ),
+            (Technique::HiddenSubset(_), _) => {
+                let mut others = self.eliminations.iter().map(|&(_, _, digit)| digit).collect::<Vec<_>>();
+                others.sort();
+                others.dedup();
+                format!(
+                    "{}: {} only go in {} in {}, eliminating {} from them",
+                    self.technique.name(), digits(&self.digits), cells(&self.cells), self.base.join(", "), digits(&others),
+                )
+            }
+            _ => format!(
+                "{}: {} in {} is confined to {}, eliminating it from {}",
+                self.technique.name(), digits(&self.digits), self.base.join(", "), self.cover.join(", "), cells(&eliminated),
+            ),
+        }
+    }
+}
+
+/// Candidates of every square, narrowed by human techniques using only the givens, the state, digit
+/// regions, odd shading, masks limiting single squares and the row, column, box and diagonal rules
+#[derive(Clone)]
+pub struct Candidates {
+    pub grid: Vec<Vec<Vec<u64>>>,
+    digits: Vec<u64>,
+    placed: Vec<Vec<bool>>,
+    units: Vec<Unit>,
+}
+
+impl Candidates {
+    pub fn new(sudoku: &Sudoku) -> Candidates {
+        let size = sudoku.size();
+        let mut grid = vec![vec![sudoku.digits.clone(); size]; size];
+        for region in &sudoku.digit_regions {
+            for cell in &region.cells {
+                grid[cell[0]][cell[1]].retain(|digit| region.digits.contains(digit));
+            }
+        }
+        if !sudoku.odd_shading.is_empty() {
+            for (i, row) in grid.iter_mut().enumerate() {
+                for (j, square) in row.iter_mut().enumerate() {
+                    let shaded = sudoku.odd_shading.iter().any(|cell| cell[0] == i && cell[1] == j);
+                    square.retain(|digit| (digit % 2 == 1) == shaded);
+                }
+            }
+        }
+        for (cells, odd) in [(&sudoku.odd_cells, true), (&sudoku.even_cells, false)] {
+            for cell in cells {
+                grid[cell[0]][cell[1]].retain(|digit| (digit % 2 == 1) == odd);
+            }
+        }
+        for quadruple in sudoku.quadruples.iter().filter(|quadruple| quadruple.digits.len() == 4) {
+            for cell in quadruple.cells() {
+                grid[cell[0]][cell[1]].retain(|digit| quadruple.digits.contains(digit));
+            }
+        }
+        for mask in &sudoku.masks {
+            for cell in &mask.cells {
+                grid[cell[0]][cell[1]].retain(|&digit| mask.rule.allows(digit));
+            }
+        }
+        for mark in &sudoku.state.pencilmarks {
+            grid[mark.cell[0]][mark.cell[1]].retain(|digit| mark.digits.contains(digit));
+        }
+
+        let mut units = Vec::new();
+        if sudoku.horizontal_rule {
+            for i in 0..size {
+                units.push(Unit { name: format!("row {i}"), kind: UnitKind::Row, cells: (0..size).map(|j| (i, j)).collect() });
+            }
+        }
+        if sudoku.vertical_rule {
+            for j in 0..size {
+                units.push(Unit { name: format!("column {j}"), kind: UnitKind::Column, cells: (0..size).map(|i| (i, j)).collect() });
+            }
+        }
+        if sudoku.nonet_rule {
+            for (b, cells) in nonets(size).into_iter().enumerate() {
+                units.push(Unit { name: format!("box {b}"), kind: UnitKind::Box, cells });
+            }
+        }
+        if sudoku.windoku {
+            for (w, cells) in windows(size).into_iter().enumerate() {
+                units.push(Unit { name: format!("window {w}"), kind: UnitKind::Box, cells });
+            }
+        }
+        if sudoku.disjoint_groups {
+            for (g, cells) in disjoint_groups(size).into_iter().enumerate() {
+                units.push(Unit { name: format!("disjoint group {g}"), kind: UnitKind::Group, cells });
+            }
+        }
+        if sudoku.positive_diagonal {
+            units.push(Unit { name: "positive diagonal".to_string(), kind: UnitKind::Diagonal, cells: (0..size).map(|k| (size - 1 - k, k)).collect() });
+        }
+        if sudoku.negative_diagonal {
+            units.push(Unit { name: "negative diagonal".to_string(), kind: UnitKind::Diagonal, cells: (0..size).map(|k| (k, k)).collect() });
+        }
+
+        let mut candidates = Candidates { grid, digits: sudoku.digits.clone(), placed: vec![vec![false; size]; size], units };
+        for (i, row) in sudoku.given.iter().enumerate() {
+            for (j, given) in row.iter().enumerate() {
+                let filled = sudoku.state.filled.get(i).and_then(|row| row[j]);
+                if let Some(digit) = given.or(filled) {
+                    let step = candidates.placement(Technique::NakedSingle, i, j, digit, Vec::new());
+                    candidates.apply(&step);
+                }
+            }
+        }
+        candidates
+    }
+
+    fn peers(&self, i: usize, j: usize) -> Vec<(usize, usize)> {
+        let mut peers = self.units.iter()
+            .filter(|unit| unit.cells.contains(&(i, j)))
+            .flat_map(|unit| unit.cells.iter().copied())
+            .filter(|&cell| cell != (i, j))
+            .collect::<Vec<_>>();
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+
+    fn placement(&self, technique: Technique, i: usize, j: usize, digit: u64, base: Vec<String>) -> Step {
+        let eliminations = self.peers(i, j).into_iter()
+            .filter(|&(k, l)| self.grid[k][l].contains(&digit))
+            .map(|(k, l)| (k, l, digit))
+            .collect();
+        Step { technique, digits: vec![digit], cells: vec![(i, j)], base, cover: Vec::new(), placement: Some((i, j, digit)), eliminations }
+    }
+
+    fn naked_single(&self) -> Option<Step> {
+        for (i, row) in self.grid.iter().enumerate() {
+            for (j, digits) in row.iter().enumerate() {
+                if !self.placed[i][j] && digits.len() == 1 {
+                    return Some(self.placement(Technique::NakedSingle, i, j, digits[0], Vec::new()));
+                }
+            }
+        }
+        None
+    }
+
+    fn hidden_single(&self) -> Option<Step> {
+        for unit in &self.units {
+            for &digit in &self.digits {
+                let spots = unit.cells.iter().filter(|&&(i, j)| self.grid[i][j].contains(&digit)).collect::<Vec<_>>();
+                if let [&(i, j)] = spots[..] {
+                    if !self.placed[i][j] {
+                        return Some(self.placement(Technique::HiddenSingle, i, j, digit, vec![unit.name.clone()]));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// A digit confined to the intersection of two units is eliminated from the rest of the second,
+    /// pointing when the first unit is a box and claiming when it is a row or column
+    fn locked_candidates(&self, technique: Technique) -> Option<Step> {
+        let pointing = technique == Technique::Pointing;
+        for base in self.units.iter().filter(|unit| (unit.kind == UnitKind::Box) == pointing) {
+            for cover in self.units.iter().filter(|unit| (unit.kind == UnitKind::Box) != pointing) {
+                for &digit in &self.digits {
+                    let spots = base.cells.iter().filter(|&&(i, j)| self.grid[i][j].contains(&digit)).collect::<Vec<_>>();
+                    if spots.len() < 2 || !spots.iter().all(|cell| cover.cells.contains(cell)) {
+                        continue;
+                    }
+                    let eliminations = cover.cells.iter()
+                        .filter(|&&(i, j)| !base.cells.contains(&(i, j)) && self.grid[i][j].contains(&digit))
+                        .map(|&(i, j)| (i, j, digit))
+                        .collect::<Vec<_>>();
+                    if !eliminations.is_empty() {
+                        return Some(Step {
+                            technique,
+                            digits: vec![digit],
+                            cells: spots.into_iter().copied().collect(),
+                            base: vec![base.name.clone()],
+                            cover: vec![cover.name.clone()],
+                            placement: None,
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn open_cells(&self, unit: &Unit) -> Vec<(usize, usize)> {
+        unit.cells.iter().copied().filter(|&(i, j)| !self.placed[i][j]).collect()
+    }
+
+    fn naked_subset(&self, n: usize) -> Option<Step> {
+        for unit in &self.units {
+            let open = self.open_cells(unit);
+            for combination in combinations(open.len(), n) {
+                let cells = combination.iter().map(|&k| open[k]).collect::<Vec<_>>();
+                let mut digits = cells.iter().flat_map(|&(i, j)| self.grid[i][j].iter().copied()).collect::<Vec<_>>();
+                digits.sort();
+                digits.dedup();
+                if digits.len() != n {
+                    continue;
+                }
+                let eliminations = open.iter()
+                    .filter(|cell| !cells.contains(cell))
+                    .flat_map(|&(i, j)| self.grid[i][j].iter().filter(|digit| digits.contains(digit)).map(move |&digit| (i, j, digit)))
+                    .collect::<Vec<_>>();
+                if !eliminations.is_empty() {
+                    let base = vec![unit.name.clone()];
+                    return Some(Step { technique: Technique::NakedSubset(n), digits, cells, base, cover: Vec::new(), placement: None, eliminations });
+                }
+            }
+        }
+        None
+    }
+
+    fn hidden_subset(&self, n: usize) -> Option<Step> {
+        for unit in &self.units {
+            let open = self.open_cells(unit);
+            let missing = self.digits.iter().copied().filter(
+                |digit| !unit.cells.iter().any(|&(i, j)| self.placed[i][j] && self.grid[i][j][0] == *digit)
+            ).collect::<Vec<_>>();
+            for combination in combinations(missing.len(), n) {
+                let digits = combination.iter().map(|&k| missing[k]).collect::<Vec<_>>();
+                let cells = open.iter().copied().filter(
+                    |&(i, j)| self.grid[i][j].iter().any(|digit| digits.contains(digit))
+                ).collect::<Vec<_>>();
+                if cells.len() != n {
+                    continue;
+                }
+                let eliminations = cells.iter()
+                    .flat_map(|&(i, j)| self.grid[i][j].iter().filter(|digit| !digits.contains(digit)).map(move |&digit| (i, j, digit)))
+                    .collect::<Vec<_>>();
+                if !eliminations.is_empty() {
+                    let base = vec![unit.name.clone()];
+                    return Some(Step { technique: Technique::HiddenSubset(n), digits, cells, base, cover: Vec::new(), placement: None, eliminations });
+                }
+            }
+        }
+        None
+    }
+
+    /// A digit whose squares in n rows (or columns) lie in only n columns (or rows) is eliminated from the
+    /// rest of those columns (or rows)
+    fn fish(&self, n: usize) -> Option<Step> {
+        for (base_kind, cover_kind) in [(UnitKind::Row, UnitKind::Column), (UnitKind::Column, UnitKind::Row)] {
+            let covers = self.units.iter().filter(|unit| unit.kind == cover_kind).collect::<Vec<_>>();
+            for &digit in &self.digits {
+                let spots = |unit: &Unit| unit.cells.iter().copied().filter(|&(i, j)| self.grid[i][j].contains(&digit)).collect::<Vec<_>>();
+                let lines = self.units.iter().filter(|unit| unit.kind == base_kind).filter(|unit| {
+                    let spots = spots(unit);
+                    spots.len() >= 2 && spots.len() <= n && spots.iter().all(|&(i, j)| !self.placed[i][j])
+                }).collect::<Vec<_>>();
+                for combination in combinations(lines.len(), n) {
+                    let base = combination.iter().map(|&k| lines[k]).collect::<Vec<_>>();
+                    let cells = base.iter().flat_map(|unit| spots(unit)).collect::<Vec<_>>();
+                    let cover = covers.iter().filter(|unit| cells.iter().any(|cell| unit.cells.contains(cell))).collect::<Vec<_>>();
+                    if cover.len() != n {
+                        continue;
+                    }
+                    let eliminations = cover.iter()
+                        .flat_map(|unit| spots(unit))
+                        .filter(|cell| !cells.contains(cell))
+                        .map(|(i, j)| (i, j, digit))
+                        .collect::<Vec<_>>();
+                    if !eliminations.is_empty() {
+                        return Some(Step {
+                            technique: Technique::Fish(n),
+                            digits: vec![digit],
+                            cells,
+                            base: base.iter().map(|unit| unit.name.clone()).collect(),
+                            cover: cover.iter().map(|unit| unit.name.clone()).collect(),
+                            placement: None,
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// The first deduction of one technique, None for chains, which need the solver
+    fn find(&self, technique: Technique) -> Option<Step> {
+        match technique {
+            Technique::NakedSingle => self.naked_single(),
+            Technique::HiddenSingle => self.hidden_single(),
+            Technique::Pointing | Technique::Claiming => self.locked_candidates(technique),
+            Technique::NakedSubset(n) => self.naked_subset(n),
+            Technique::HiddenSubset(n) => self.hidden_subset(n),
+            Technique::Fish(n) => self.fish(n),
+            Technique::Chain => None,
+        }
+    }
+
+    /// The first deduction of every technique that has one, in the order of TECHNIQUES. Techniques are only
+    /// searched as the iterator is advanced.
+    pub fn deductions(&self) -> impl Iterator<Item = Step> + '_ {
+        TECHNIQUES.into_iter().filter_map(|technique| self.find(technique))
+    }
+
+    /// The easiest deduction available
+    pub fn next_step(&self) -> Option<Step> {
+        self.deductions().next()
+    }
+
+    pub fn apply(&mut self, step: &Step) {
+        if let Some((i, j, digit)) = step.placement {
+            self.grid[i][j] = vec![digit];
+            self.placed[i][j] = true;
+        }
+        for &(i, j, digit) in &step.eliminations {
+            self.grid[i][j].retain(|&candidate| candidate != digit);
+        }
+    }
+
+    /// Applies deductions until none is left or a square runs out of candidates
+    pub fn solve(&mut self) -> Vec<Step> {
+        self.solve_up_to(usize::MAX)
+    }
+
+    /// Like solve, but gives up after the given number of steps
+    pub fn solve_up_to(&mut self, limit: usize) -> Vec<Step> {
+        let mut steps = Vec::new();
+        while !self.contradiction() && steps.len() < limit {
+            match self.next_step() {
+                Some(step) => {
+                    self.apply(&step);
+                    steps.push(step);
+                }
+                None => break,
+            }
+        }
+        steps
+    }
+
+    /// Candidates a step relies on being already eliminated when applied to these candidates, None for a chain,
+    /// which relies on everything eliminated before it
+    fn premises(&self, step: &Step) -> Option<Vec<(usize, usize, u64)>> {
+        let absent = |cells: &[(usize, usize)], digits: &[u64]| cells.iter()
+            .flat_map(|&(i, j)| digits.iter().filter(move |digit| !self.grid[i][j].contains(digit)).map(move |&digit| (i, j, digit)))
+            .collect::<Vec<_>>();
+        let outside = |units: &[String]| self.units.iter()
+            .filter(|unit| units.contains(&unit.name))
+            .flat_map(|unit| unit.cells.iter().copied())
+            .filter(|cell| !step.cells.contains(cell))
+            .collect::<Vec<_>>();
+        match step.technique {
+            Technique::NakedSingle | Technique::NakedSubset(_) => {
+                let others = self.digits.iter().copied().filter(|digit| !step.digits.contains(digit)).collect::<Vec<_>>();
+                Some(absent(&step.cells, &others))
+            }
+            Technique::HiddenSubset(_) => {
+                let open = outside(&step.base).into_iter().filter(|&(i, j)| !self.placed[i][j]).collect::<Vec<_>>();
+                Some(absent(&open, &step.digits))
+            }
+            Technique::HiddenSingle | Technique::Pointing | Technique::Claiming | Technique::Fish(_) => Some(absent(&outside(&step.base), &step.digits)),
+            Technique::Chain => None,
+        }
+    }
+
+    /// Indices of the steps, applied in order from these candidates, that a solve path needs. Every placement is
+    /// needed, along with every elimination step that removed a candidate a needed step relies on.
+    pub fn needed_steps(&self, steps: &[Step]) -> Vec<usize> {
+        let mut candidates = self.clone();
+        let mut eliminated_by = std::collections::HashMap::new();
+        let mut depends = Vec::new();
+        for (index, step) in steps.iter().enumerate() {
+            depends.push(match candidates.premises(step) {
+                Some(premises) => premises.iter().filter_map(|premise| eliminated_by.get(premise).copied()).collect::<Vec<usize>>(),
+                None => (0..index).collect(),
+            });
+            let mut removed = step.eliminations.clone();
+            if let Some((i, j, digit)) = step.placement {
+                removed.extend(candidates.grid[i][j].iter().filter(|&&other| other != digit).map(|&other| (i, j, other)));
+            }
+            for elimination in removed {
+                eliminated_by.entry(elimination).or_insert(index);
+            }
+            candidates.apply(step);
+        }
+        let mut needed = vec![false; steps.len()];
+        let mut pending = steps.iter().enumerate().filter(|(_, step)| step.placement.is_some()).map(|(index, _)| index).collect::<Vec<_>>();
+        while let Some(index) = pending.pop() {
+            if !needed[index] {
+                needed[index] = true;
+                pending.extend(&depends[index]);
+            }
+        }
+        (0..steps.len()).filter(|&index| needed[index]).collect()
+    }
+
+    /// The step placing a digit in a square, as if it were given
+    pub fn assume(&self, i: usize, j: usize, digit: u64) -> Step {
+        self.placement(Technique::Chain, i, j, digit, Vec::new())
+    }
+
+    pub fn solved(&self) -> bool {
+        self.placed.iter().flatten().all(|&placed| placed)
+    }
+
+    pub fn contradiction(&self) -> bool {
+        self.dead_end().is_some()
+    }
+
+    /// A square without candidates or a digit without a square in some unit, if there is one
+    pub fn dead_end(&self) -> Option<String> {
+        for (i, row) in self.grid.iter().enumerate() {
+            for (j, digits) in row.iter().enumerate() {
+                if digits.is_empty() {
+                    return Some(format!("r{i}c{j} has no candidates left"));
+                }
+            }
+        }
+        for unit in &self.units {
+            for digit in &self.digits {
+                if !unit.cells.iter().any(|&(i, j)| self.grid[i][j].contains(digit)) {
+                    return Some(format!("{digit} has no square left in {}", unit.name));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic_sudoku;
+
+    const WIKI: [&str; 9] = [
+        "53..7....", "6..195...", ".98....6.", "8...6...3", "4..8.3..1", "7...2...6", ".6....28.", "...419..5", "....8..79",
+    ];
+    const EMPTY: [&str; 9] = ["........."; 9];
+
+    fn empty() -> Candidates {
+        Candidates::new(&classic_sudoku(&EMPTY))
+    }
+
+    fn remove(candidates: &mut Candidates, cells: impl IntoIterator<Item = (usize, usize)>, digit: u64) {
+        for (i, j) in cells {
+            candidates.grid[i][j].retain(|&d| d != digit);
+        }
+    }
+
+    #[test]
+    fn solves_with_singles() {
+        let mut candidates = Candidates::new(&classic_sudoku(&WIKI));
+        let steps = candidates.solve();
+        assert!(candidates.solved());
+        assert!(steps.iter().all(|step| matches!(step.technique, Technique::NakedSingle | Technique::HiddenSingle)));
+        let first_row = candidates.grid[0].iter().map(|digits| digits[0]).collect::<Vec<_>>();
+        assert_eq!(first_row, [5, 3, 4, 6, 7, 8, 9, 1, 2]);
+    }
+
+    #[test]
+    fn naked_single() {
+        let mut candidates = empty();
+        candidates.grid[4][4] = vec![7];
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::NakedSingle);
+        assert_eq!(step.placement, Some((4, 4, 7)));
+        assert_eq!(step.eliminations.len(), 20);
+    }
+
+    #[test]
+    fn hidden_single() {
+        let mut candidates = empty();
+        remove(&mut candidates, (0..9).filter(|&j| j != 4).map(|j| (0, j)), 7);
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::HiddenSingle);
+        assert_eq!(step.placement, Some((0, 4, 7)));
+        assert_eq!(step.base, ["row 0"]);
+    }
+
+    #[test]
+    fn pointing() {
+        let mut candidates = empty();
+        remove(&mut candidates, (1..3).flat_map(|i| (0..3).map(move |j| (i, j))), 5);
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::Pointing);
+        assert_eq!((step.base, step.cover), (vec!["box 0".to_string()], vec!["row 0".to_string()]));
+        assert_eq!(step.eliminations, (3..9).map(|j| (0, j, 5)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn claiming() {
+        let mut candidates = empty();
+        remove(&mut candidates, (3..9).map(|j| (0, j)), 5);
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::Claiming);
+        assert_eq!((step.base, step.cover), (vec!["row 0".to_string()], vec!["box 0".to_string()]));
+        assert_eq!(step.eliminations, (1..3).flat_map(|i| (0..3).map(move |j| (i, j, 5))).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn naked_pair() {
+        let mut candidates = empty();
+        candidates.grid[0][0] = vec![1, 2];
+        candidates.grid[0][1] = vec![1, 2];
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::NakedSubset(2));
+        assert_eq!((step.digits, step.cells), (vec![1, 2], vec![(0, 0), (0, 1)]));
+        assert_eq!(step.eliminations.len(), 14);
+    }
+
+    #[test]
+    fn hidden_pair() {
+        let mut candidates = empty();
+        for digit in [1, 2] {
+            remove(&mut candidates, (2..9).map(|j| (0, j)), digit);
+        }
+        // Both digits are also confined to box 0, so claiming would come first
+        let step = candidates.hidden_subset(2).unwrap();
+        assert_eq!(step.technique, Technique::HiddenSubset(2));
+        assert_eq!((step.digits, step.cells), (vec![1, 2], vec![(0, 0), (0, 1)]));
+        assert_eq!(step.eliminations.len(), 14);
+    }
+
+    #[test]
+    fn deductions_easiest_first() {
+        let mut candidates = empty();
+        remove(&mut candidates, (3..9).map(|j| (0, j)), 5);
+        candidates.grid[8][0] = vec![1, 2];
+        candidates.grid[8][1] = vec![1, 2];
+        let techniques = candidates.deductions().map(|step| step.technique).collect::<Vec<_>>();
+        assert_eq!(techniques, [Technique::Claiming, Technique::NakedSubset(2)]);
+    }
+
+    #[test]
+    fn x_wing() {
+        let mut candidates = empty();
+        remove(&mut candidates, [0, 4].into_iter().flat_map(|i| (0..9).filter(|&j| j != 0 && j != 6).map(move |j| (i, j))), 5);
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique, Technique::Fish(2));
+        assert_eq!((step.base, step.cover), (vec!["row 0".to_string(), "row 4".to_string()], vec!["column 0".to_string(), "column 6".to_string()]));
+        assert_eq!(step.eliminations.len(), 14);
+    }
+
+    #[test]
+    fn jellyfish() {
+        let mut candidates = empty();
+        remove(&mut candidates, [0, 2, 4, 6].into_iter().flat_map(|i| [1, 3, 5, 7, 8].map(|j| (i, j))), 5);
+        let step = candidates.next_step().unwrap();
+        assert_eq!(step.technique.name(), "Jellyfish");
+        assert_eq!(step.eliminations.len(), 20);
+        assert!(step.eliminations.iter().all(|&(i, j, digit)| i % 2 == 1 || i == 8 && j % 2 == 0 && digit == 5));
+    }
+}
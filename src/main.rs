@@ -1,272 +1,1343 @@
-mod solver;
-mod optimize;
+mod notify;
+mod config;
+mod completions;
+mod sigint;
 
-use std::{fs::File, io::BufReader};
-use clap::{Parser, ValueEnum};
+use std::{ffi::OsString, fs::File, io::BufReader, time::{Duration, Instant}};
+use clap::{error::ErrorKind, Arg, Command, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use serde_json::*;
-use z3::{SatResult, Solver, Model, Optimize};
+use z3::{Context, SatResult, Solver, Model, Optimize};
 use z3::ast::{Ast, Int, Bool};
 
-use crate::solver::add_solver_constraints;
-use crate::optimize::add_optimizer_constraints;
-
-#[derive(Debug)]
-struct Sudoku {
-    given: Vec<Vec<u64>>,
-    horizontal_rule: bool,
-    vertical_rule: bool,
-    nonet_rule: bool,
-    offset: Vec<Vec<i32>>,
-    thermo: Vec<Vec<Vec<usize>>>,
-    arrow: Vec<Vec<Vec<usize>>>,
-    kropki_adjacent: Vec<Vec<Vec<usize>>>,
-    kropki_double: Vec<Vec<Vec<usize>>>,
-    german_whispers: Vec<Vec<Vec<usize>>>
-}
+use sudoku_z3::{block_solution, cage_sum, count_up_to, new_grid, open_sudoku, parse_cell, parse_restriction, print_candidate_grid, print_sudoku_from_model, print_unknowns, solution_from_model, Adjacency, Heatmap, Session, Side, State, Style, Sudoku, STYLE};
+use sudoku_z3::solver::add_solver_constraints;
+use sudoku_z3::optimize::{add_optimizer_constraints, soft_constraint_ast};
+use sudoku_z3::estimate::estimate_solutions;
+use sudoku_z3::orient::orient_lines;
+use sudoku_z3::normalize::{line_gaps, normalize, sudoku_to_json, validate};
+use sudoku_z3::clues::{fill_border_clues, fill_cage_sums, needed_cage_sums};
+use sudoku_z3::objective::parse_linear;
+use sudoku_z3::placement::place_unknown_givens;
+use sudoku_z3::fog::simulate_fog;
+use sudoku_z3::fixtures::verify_install;
+use sudoku_z3::describe::describe;
+use sudoku_z3::delta::candidate_delta;
+use sudoku_z3::logic::Candidates;
+use sudoku_z3::chain::find_chain;
+use sudoku_z3::sample::sample_solutions;
+use sudoku_z3::profile::profile_families;
+use sudoku_z3::parallel::count_parallel;
+use sudoku_z3::mistakes::check_progress;
+use sudoku_z3::trainer::next_safe_digit;
+use sudoku_z3::heatmap::show_heatmap;
+use sudoku_z3::projection::{parse_projection, project};
+use sudoku_z3::multigrid::solve_linked;
+use sudoku_z3::relabel::{canonical_form, fingerprint, relabel};
+use sudoku_z3::analyze::given_distribution;
+use sudoku_z3::ranges::range_hints;
+use sudoku_z3::sudokupad::progress_url;
+use sudoku_z3::rng;
+#[cfg(feature = "sat")]
+use sudoku_z3::{print_solution, sat::encode};
+use sudoku_z3::interrupt::{interrupted, interrupted_query};
+use crate::notify::notify;
+use crate::config::config_args;
+use crate::completions::completions;
+use crate::sigint::{interruptible, EXIT_INTERRUPTED};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Subcommand)]
 enum Mode {
     /// Find a solution of the sudoku
-    Solution,
+    #[command(name = "solve", alias = "solution")]
+    Solution {
+        /// Narrow every square to the candidates left by human techniques before solving
+        #[arg(long)]
+        prune: bool,
+
+        /// How to print the results
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
 
     /// Find the number of solutions of the sudoku (up to max_sudoku)
-    Count,
+    Count {
+        /// Maximum number of Sudokus to search
+        #[arg(long, default_value_t = 1000)]
+        max_sudoku: u32,
+
+        /// Narrow every square to the candidates left by human techniques before solving
+        #[arg(long)]
+        prune: bool,
+
+        /// Square such as r1c1 whose every value gets its own count
+        #[arg(long)]
+        count_by: Option<String>,
+
+        /// Square such as r0c0 whose values split the count across threads (can be repeated)
+        #[arg(long)]
+        split: Vec<String>,
+
+        /// How to print the results
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
 
     /// Find the possible answers in each square
-    Hint,
+    Hint {
+        /// Maximum number of Sudokus to search
+        #[arg(long, default_value_t = 1000)]
+        max_sudoku: u32,
+
+        /// Stop as soon as the first solution is proven unique
+        #[arg(long)]
+        stop_when_unique: bool,
+
+        /// Stop refining candidates after this many seconds and print the best sets known so far
+        #[arg(long)]
+        budget: Option<u64>,
+
+        /// Colour every square by how many candidates it has left
+        #[arg(long, value_enum)]
+        heatmap: Option<Heatmap>,
+
+        /// File path to write an SVG heatmap to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
     /// Find the possible answers in a single square
-    Square,
+    Square {
+        /// Row of the square to find all possible answers
+        #[arg(short, long)]
+        row: usize,
+
+        /// Column of the square to find all possible answers
+        #[arg(short, long)]
+        col: usize,
+    },
+
+    /// Estimate the number of solutions of the sudoku using random XOR constraints
+    Estimate {
+        /// Tolerance of the estimate, which is within a factor of 1 + epsilon of the true count
+        #[arg(long, default_value_t = 0.8, value_parser = parse_epsilon)]
+        epsilon: f64,
+        /// Chance that the estimate falls outside the tolerance, which sets the number of hashing trials
+        #[arg(long, default_value_t = 0.2, value_parser = parse_delta)]
+        delta: f64,
+    },
+
+    /// Find which orientations of the thermos and arrows give a unique sudoku
+    Orient,
+
+    /// Validate the sudoku and write it back out as canonical JSON
+    Normalize {
+        /// File path to write to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Compute sandwich, X-sum, skyscraper and frame clues from the solution of the sudoku
+    Clues {
+        /// Border position to compute clues for such as left:0 or top:4 (defaults to every position)
+        #[arg(long, value_parser = parse_border)]
+        border: Vec<(Side, usize)>,
+
+        /// File path to write the puzzle with its clues to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Compute the killer cage sums from a solution of the sudoku
+    CageSums {
+        /// File path containing JSON of the solution grid (found by the solver when absent)
+        #[arg(long)]
+        solution: Option<String>,
+
+        /// Keep only the sums needed for the solution to be unique
+        #[arg(long)]
+        minimize: bool,
+
+        /// File path to write the puzzle with its cage sums to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Find the solution breaking the least total weight of soft constraints
+    Repair,
+
+    /// Find the solution maximizing or minimizing a linear objective over squares
+    Objective {
+        /// Linear expression over squares to maximize such as "r0c0 + r1c1 + 2*r2c2"
+        #[arg(long, conflicts_with = "minimize", required_unless_present = "minimize")]
+        maximize: Option<String>,
+
+        /// Linear expression over squares to minimize
+        #[arg(long)]
+        minimize: Option<String>,
+    },
+
+    /// Find values for the unknown givens that make the sudoku unique (up to max_sudoku)
+    Placement {
+        /// Maximum number of Sudokus to search
+        #[arg(long, default_value_t = 1000)]
+        max_sudoku: u32,
+    },
+
+    /// Check that the sudoku can be solved by revealing the fog one deduction at a time
+    Fog,
+
+    /// Show which candidates an edited version of the sudoku eliminates or reintroduces
+    Delta {
+        /// File path containing JSON of the edited Sudoku
+        #[arg(long)]
+        compare: String,
+    },
+
+    /// Solve with human techniques on the givens and row, column and box rules, printing every step
+    Logic {
+        /// Colour every square by how many candidates it has left
+        #[arg(long, value_enum)]
+        heatmap: Option<Heatmap>,
+
+        /// File path to write an SVG heatmap to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Draw distinct solutions roughly uniformly at random
+    Sample {
+        /// Number of distinct solutions to draw
+        #[arg(long, default_value_t = 5)]
+        samples: u32,
+    },
+
+    /// Time solving with each constraint family removed to find the expensive ones
+    Profile {
+        /// Number of timed runs
+        #[arg(long, default_value_t = 5)]
+        trials: u32,
+    },
+
+    /// Write the generated constraints as an SMT-LIB2 file for other solvers
+    ExportSmt {
+        /// File path to write to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Check the filled digits and pencilmarks of the state against the intended solution
+    Mistakes {
+        /// File path containing JSON of the intended solution grid (found by the solver when absent)
+        #[arg(long)]
+        solution: Option<String>,
+    },
+
+    /// Point out the easiest square to fill next without giving away the rest of the solution
+    Next {
+        /// Also show the digit of the square
+        #[arg(long)]
+        reveal: bool,
+    },
+
+    /// Print a SudokuPad link with the givens, the state and the digits and candidates forced so far filled in
+    Sudokupad,
+
+    /// Find every value expressions over the solution take and whether they are the same in all solutions
+    Project {
+        /// Maximum number of Sudokus to search
+        #[arg(long, default_value_t = 1000)]
+        max_sudoku: u32,
+
+        /// Expression over the solution such as "sum(r1)", "diag" or "r0c0 + r1c1" (can be repeated)
+        #[arg(long, required = true)]
+        project: Vec<String>,
+    },
+
+    /// Solve the grids listed in a hunt file together with the links between their squares
+    Hunt,
+
+    /// Print an equivalent puzzle with its digits permuted and its grid turned or mirrored at random
+    Relabel {
+        /// File path to write to
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Report on the givens of the puzzle for setters
+    Analyze,
+
+    /// Print a hash that is the same for puzzles equal up to relabelling, turning and mirroring
+    Fingerprint {
+        /// Directory of puzzles to check for ones that are the same up to relabelling
+        #[arg(long)]
+        archive: Option<String>,
+    },
+
+    /// Solve the built-in reference puzzles and check the results
+    #[command(alias = "verify-install")]
+    Verify,
+
+    /// Print a completion script for the shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Grids and messages for reading in a terminal
+    Text,
+
+    /// One JSON object per line, printed as soon as each result is found
+    Ndjson,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Backend {
+    /// Z3 with integer squares, which handles every rule
+    Z3,
+
+    /// The pure-Rust DPLL search over one boolean per square and digit, for the classic rules and variants
+    /// relating pairs of squares, built with the sat feature
+    Sat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
 }
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_override_self = true)]
 struct Args {
+    #[command(subcommand)]
+    mode: Mode,
+
     /// File path containing JSON of Sudoku
-    #[arg(short, long)]
-    file_path: String,
+    #[arg(short, long, global = true)]
+    file_path: Option<String>,
 
-    /// What mode to run the solver in
-    #[arg(long, value_enum)]
-    mode: Mode,
+    /// How to draw printed grids
+    #[arg(long, value_enum, default_value_t = Style::Unicode, global = true)]
+    style: Style,
+
+    /// URL of a webhook, such as a Discord one, to post a summary to when the run finishes
+    #[arg(long, global = true)]
+    notify: Option<String>,
+
+    /// How consecutive squares of thermos, arrows and whispers must touch
+    #[arg(long, value_enum, default_value_t = Adjacency::Diagonal, global = true)]
+    adjacency: Adjacency,
+
+    /// Limit a square to some digits for this run, such as r5c5=2,4,6 (can be repeated)
+    #[arg(long, global = true)]
+    restrict: Vec<String>,
 
-    /// Maximum number of Sudokus to search
-    #[arg(long, default_value_t = 1000)]
-    max_sudoku: u32,
+    /// Print every parsed constraint before solving
+    #[arg(short, long, global = true)]
+    verbose: bool,
 
-    /// Use with Square, row of the square to find all possible answers
-    #[arg(short, long)]
-    row: Option<usize>,
+    /// Seed for randomized modes, defaults to the current time
+    #[arg(long, global = true)]
+    seed: Option<u64>,
 
-    /// Use with Square, column of the square to find all possible answers
-    #[arg(short, long)]
-    col: Option<usize>,
+    /// Solver to solve and count with
+    #[arg(long, value_enum, default_value_t = Backend::Z3, global = true)]
+    backend: Backend,
 }
 
-fn open_sudoku(fp: &String) -> Sudoku {
-    let file = File::open(fp).unwrap();
-    let reader = BufReader::new(file);
-    let v: Value = serde_json::from_reader(reader).unwrap();
-
-    Sudoku {
-        given: serde_json::from_value(v["given"].clone()).unwrap(),
-        horizontal_rule: serde_json::from_value(v["1-9horiz"].clone()).unwrap(),
-        vertical_rule: serde_json::from_value(v["1-9vert"].clone()).unwrap(),
-        nonet_rule: serde_json::from_value(v["1-9nonet"].clone()).unwrap(),
-        offset: serde_json::from_value(v["offsets"].clone()).unwrap(),
-        thermo: serde_json::from_value(v["thermo"].clone()).unwrap(),
-        arrow: serde_json::from_value(v["arrow"].clone()).unwrap(),
-        kropki_adjacent: serde_json::from_value(v["kropkiAdjacent"].clone()).unwrap(),
-        kropki_double: serde_json::from_value(v["kropkiDouble"].clone()).unwrap(),
-        german_whispers: serde_json::from_value(v["germanWhispers"].clone()).unwrap(),
+/// Parses a border position written as side:index, leaving the check that the index is in the grid for later
+fn parse_border(value: &str) -> std::result::Result<(Side, usize), String> {
+    let (side, index) = value.split_once(':').ok_or("expected a side and an index such as left:0")?;
+    let side = [Side::Left, Side::Right, Side::Top, Side::Bottom].into_iter().find(|known| known.name() == side)
+        .ok_or_else(|| format!("unknown side {side}, expected left, right, top or bottom"))?;
+    let index = index.parse().map_err(|_| format!("expected a row or column number after {}:", side.name()))?;
+    Ok((side, index))
+}
+
+fn parse_epsilon(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(epsilon) if epsilon > 0.0 => Ok(epsilon),
+        _ => Err("expected a tolerance above 0".to_string()),
     }
 }
 
-fn print_sudoku_from_model(model: &Model, grid: &Vec<Vec<Int<'_>>>) {
-    let mut sudoku = [[0; 9]; 9];
-    for i in 0..9 {
-        for j in 0..9 {
-            sudoku[i][j] =  model.get_const_interp(&grid[i][j]).unwrap().as_u64().unwrap();
+fn parse_delta(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(delta) if delta > 0.0 && delta < 1.0 => Ok(delta),
+        _ => Err("expected a probability between 0 and 1".to_string()),
+    }
+}
+
+/// A solution and the values of the unknowns as one JSON object
+fn solution_json(sudoku: &Sudoku, model: &Model, grid: &[Vec<Int<'_>>], ctx: &Context) -> Value {
+    let mut value = json!({"solution": solution_from_model(model, grid)});
+    if !sudoku.unknowns.is_empty() {
+        value["unknowns"] = sudoku.unknowns.iter().map(|unknown| {
+            let solved = model.eval(&Int::new_const(ctx, unknown.name.as_str()), true).unwrap().as_i64();
+            (unknown.name.clone(), json!(solved))
+        }).collect::<Map<_, _>>().into();
+    }
+    let hidden = sudoku.killer_cages.iter().enumerate().filter(|(_, cage)| cage.sum.is_none()).map(|(i, _)| i).collect::<Vec<_>>();
+    if !hidden.is_empty() {
+        value["cageSums"] = hidden.iter().map(|&i| {
+            (i.to_string(), json!(model.eval(&cage_sum(sudoku, i, ctx), true).unwrap().as_i64()))
+        }).collect::<Map<_, _>>().into();
+    }
+    value
+}
+
+fn print_hint_summary(candidates: &[Vec<Vec<u64>>]) {
+    let mut determined = 0;
+    let mut remaining = 0;
+    let mut undetermined = Vec::new();
+    for (i, row) in candidates.iter().enumerate() {
+        for (j, digits) in row.iter().enumerate() {
+            remaining += digits.len();
+            match digits.len() {
+                1 => determined += 1,
+                0 => {},
+                n => undetermined.push((n, i, j)),
+            }
+        }
+    }
+    undetermined.sort();
+    println!("{determined} of {} squares are determined, {remaining} candidates remain.", candidates.len() * candidates.len());
+    if !undetermined.is_empty() {
+        println!("Most constrained undetermined squares:");
+        for (n, i, j) in undetermined.iter().take(5) {
+            println!("  Row {i} Column {j}: {n} candidates");
         }
     }
-    println!("╔═══════╤═══════╤═══════╗");
-    for i in 0..9 {
-        print!("║");
-        for j in 0..3 {
-            print!(" {} {} {} ", sudoku[i][3 * j], sudoku[i][3 * j + 1], sudoku[i][3 * j + 2]);
-            if j != 2 {
-                print!("│");
+}
+
+fn print_clues(clues: &[Vec<Vec<u64>>]) {
+    let mut candidates = clues.to_vec();
+    for digits in candidates.iter_mut().flatten() {
+        digits.sort();
+    }
+    for (i, row) in candidates.iter().enumerate() {
+        for (j, digits) in row.iter().enumerate() {
+            print!("Row {i} Column {j}: ");
+            for k in digits {
+                print!("{k} ");
             }
+            println!();
         }
-        println!("║");
-        if i % 3 == 2 && i != 8 {
-            println!("╟───────┼───────┼───────╢")
+    }
+    print_candidate_grid(&candidates, &[]);
+    print_hint_summary(&candidates);
+}
+
+/// Prints the candidates left by human techniques when the hint budget runs out, starring squares where some
+/// digits are not yet confirmed by a solution, so their candidates are only an upper bound
+fn print_budgeted_clues(bounds: &[Vec<Vec<u64>>], clues: &[Vec<Vec<u64>>]) {
+    let mut unconfirmed = Vec::new();
+    for (i, row) in bounds.iter().enumerate() {
+        for (j, digits) in row.iter().enumerate() {
+            let exact = digits.iter().all(|digit| clues[i][j].contains(digit));
+            print!("Row {i} Column {j}: ");
+            for k in digits {
+                print!("{k} ");
+            }
+            if !exact {
+                print!("*");
+                unconfirmed.push((i, j));
+            }
+            println!();
         }
     }
-    println!("╚═══════╧═══════╧═══════╝");
+    print_candidate_grid(bounds, &unconfirmed);
+    print_hint_summary(bounds);
+    println!("{} squares marked * may have fewer candidates than shown.", unconfirmed.len());
 }
 
-fn main() {
-    let args = Args::parse();
+/// How far human techniques narrow the sudoku when --prune hands their candidates to the solver
+fn pruning_summary(sudoku: &Sudoku) -> String {
+    let mut candidates = Candidates::new(sudoku);
+    let steps = candidates.solve();
+    let remaining = candidates.grid.iter().flatten().map(|digits| digits.len()).sum::<usize>();
+    format!("Pruned to {remaining} candidates with {} logical steps.", steps.len())
+}
 
-    let sudoku = open_sudoku(&args.file_path);
+/// The full version string of the Z3 library linked in
+fn z3_version() -> String {
+    unsafe { std::ffi::CStr::from_ptr(z3_sys::Z3_get_full_version()) }.to_string_lossy().into_owned()
+}
 
-    let config = z3::Config::new();
-    let ctx = z3::Context::new(&config);
+/// Versions and settings a run depends on, so its results can be reproduced
+fn run_metadata(args: &Args, mode: &str, seed: u64) -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "z3": z3_version(),
+        "mode": mode,
+        "file": args.file_path,
+        "encoding": if args.backend == Backend::Sat { "one-hot" } else { "integer" },
+        "tactic": "default",
+        "seed": seed,
+    })
+}
 
-    let grid = (0..9).map(|i: i32| (0..9).map(|j| Int::new_const(&ctx, format!("r{i}c{j}"))).collect()).collect::<Vec<Vec<_>>>();
+/// Reports how long the run took when it is dropped at the end of main, whichever way main returns, and
+/// posts the summary to the webhook if one was given
+struct RunTimer {
+    start: Instant,
+    ndjson: bool,
+    verbose: bool,
+    notify: Option<(String, Value)>,
+    /// Outcome of the run in a few words, for the webhook
+    result: Option<String>,
+}
 
-    match args.mode {
-        Mode::Solution => {
-            if args.row.is_some() || args.col.is_some() {
-                println!("Ignoring row and column information in Solution mode.");
+impl Drop for RunTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        if self.ndjson {
+            println!("{}", json!({"run": {"elapsedMs": elapsed.as_millis() as u64}}));
+        } else if self.verbose {
+            println!("Finished in {:.3}s", elapsed.as_secs_f64());
+        }
+        if let Some((url, metadata)) = &self.notify {
+            notify(url, metadata, self.result.as_deref(), elapsed);
+        }
+    }
+}
+
+/// Long names of the flags given on the command line, short ones such as `-v` included
+fn given_flags<'a>(command_line: &[OsString], arguments: &[&'a Arg]) -> Vec<&'a str> {
+    let mut given = Vec::new();
+    for arg in command_line.iter().skip(1).filter_map(|arg| arg.to_str()) {
+        if let Some(long) = arg.strip_prefix("--") {
+            let long = long.split('=').next().unwrap();
+            given.extend(arguments.iter().filter_map(|known| known.get_long()).filter(|&known| known == long));
+        } else if let Some(shorts) = arg.strip_prefix('-') {
+            for short in shorts.chars() {
+                let Some(known) = arguments.iter().find(|known| known.get_short() == Some(short)) else { break };
+                given.extend(known.get_long());
+                if known.get_action().takes_values() {
+                    break;
+                }
             }
-            let solver = Solver::new(&ctx);
-            add_solver_constraints(&sudoku, &grid, &solver, &ctx);
-            println!("Constraints added. Solver is running...");
-            match solver.check() {
-                SatResult::Sat => {
+        }
+    }
+    given
+}
+
+/// The command line with the defaults from the config files put right after the mode. Only the defaults the
+/// chosen mode accepts are used, so one file can hold the flags of several modes, and a flag given on the
+/// command line, before or after the mode, replaces its default. `--no-<flag>` drops the default of a flag
+/// that takes no value, such as `--no-verbose`, and is then removed.
+fn with_config_defaults(command: &Command, command_line: Vec<OsString>) -> Vec<OsString> {
+    let modes = command.get_subcommands().collect::<Vec<_>>();
+    let found = command_line.iter().enumerate().skip(1).find_map(|(index, arg)| {
+        let arg = arg.to_str()?;
+        modes.iter().find(|mode| mode.get_name() == arg || mode.get_all_aliases().any(|alias| alias == arg)).map(|mode| (index, mode))
+    });
+    let Some((index, mode)) = found else { return command_line };
+    let defaults = config_args().unwrap_or_else(|error| {
+        eprintln!("Could not read config file {error}");
+        std::process::exit(2);
+    });
+    let globals = command.get_arguments().filter(|arg| arg.is_global_set());
+    let arguments = mode.get_arguments().chain(globals).collect::<Vec<_>>();
+    let known = arguments.iter().filter_map(|arg| arg.get_long()).collect::<Vec<_>>();
+    let switches = arguments.iter().filter(|arg| !arg.get_action().takes_values()).filter_map(|arg| arg.get_long()).collect::<Vec<_>>();
+    let negated = |arg: &OsString| arg.to_str()?.strip_prefix("--no-").and_then(|long| switches.iter().find(|&&switch| switch == long).copied());
+    let mut given = given_flags(&command_line, &arguments);
+    given.extend(command_line.iter().filter_map(negated));
+    let mut defaults = defaults.into_iter()
+        .filter(|flag| flag[0].strip_prefix("--").is_some_and(|long| known.contains(&long) && !given.contains(&long)))
+        .flatten()
+        .map(OsString::from);
+    let mut with_defaults = Vec::new();
+    for (position, arg) in command_line.into_iter().enumerate() {
+        if negated(&arg).is_none() {
+            with_defaults.push(arg);
+        }
+        if position == index {
+            with_defaults.extend(&mut defaults);
+        }
+    }
+    with_defaults
+}
+
+/// Solves or counts with the pure-Rust backend instead of Z3
+#[cfg(feature = "sat")]
+fn solve_with_sat(sudoku: &Sudoku, mode: &Mode, ndjson: bool, timer: &mut RunTimer) {
+    let cnf = match encode(sudoku) {
+        Ok(cnf) => cnf,
+        Err(rules) => {
+            println!("The sat backend has no clauses for {}, solve with --backend z3 instead.", rules.join(", "));
+            return;
+        }
+    };
+    match *mode {
+        Mode::Solution { .. } => {
+            if !ndjson {
+                println!("Clauses added. Solver is running...");
+            }
+            match cnf.solutions(1).first() {
+                Some(solution) if ndjson => println!("{}", json!({"solution": solution})),
+                Some(solution) => {
                     println!("Possible solution found!");
-                    let model = solver.get_model().unwrap();
-                    print_sudoku_from_model(&model, &grid);
+                    print_solution(sudoku, solution);
                 },
-                SatResult::Unsat => {
-                    println!("Could not find a satisfying Sudoku.");
-                },
-                SatResult::Unknown => {
-                    panic!("Solver returned unknown!");
+                None if ndjson => println!("{}", json!({"solution": null})),
+                None => println!("Could not find a satisfying Sudoku."),
+            }
+        },
+        Mode::Count { max_sudoku, .. } => {
+            if !ndjson {
+                println!("Clauses added. Counting solutions...");
+            }
+            let solutions = cnf.solutions(max_sudoku as usize);
+            let capped = solutions.len() == max_sudoku as usize;
+            if ndjson {
+                for solution in &solutions {
+                    println!("{}", json!({"solution": solution}));
                 }
+                println!("{}", json!({"count": solutions.len(), "capped": capped}));
+            } else if capped {
+                println!("Found >{} possible sudokus!", max_sudoku);
+            } else {
+                println!("Found {} possible sudokus!", solutions.len());
+            }
+            timer.result = Some(format!("{}{} solutions", if capped { ">" } else { "" }, solutions.len()));
+        },
+        _ => unreachable!("the sat backend is only accepted for solving and counting"),
+    }
+}
+
+fn main() {
+    let command = Args::command();
+    let command_line = with_config_defaults(&command, std::env::args_os().collect());
+    let matches = command.get_matches_from(command_line);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    let mode_name = matches.subcommand_name().unwrap().to_string();
+    STYLE.set(args.style).ok();
+
+    match args.mode {
+        Mode::Verify => {
+            if !verify_install() {
+                std::process::exit(1);
             }
+            return;
+        },
+        Mode::Completions { shell } => {
+            print!("{}", completions(&Args::command(), shell));
+            return;
         },
-        Mode::Count => {
-            if args.row.is_some() || args.col.is_some() {
-                println!("Ignoring row and column information in Solution mode.");
+        _ => {}
+    }
+    let Some(file_path) = args.file_path.clone() else {
+        let mut command = Args::command();
+        command.build();
+        let mode = command.find_subcommand_mut(&mode_name).unwrap();
+        mode.error(ErrorKind::MissingRequiredArgument, "the puzzle is needed, pass it with --file-path").exit();
+    };
+    if args.backend == Backend::Sat {
+        let problem = if !cfg!(feature = "sat") {
+            Some("the sat backend is not built in, rebuild with --features sat")
+        } else if !(matches!(args.mode, Mode::Solution { .. }) || matches!(&args.mode, Mode::Count { count_by: None, split, .. } if split.is_empty())) {
+            Some("the sat backend only solves and counts, without --count-by or --split")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            let mut command = Args::command();
+            command.build();
+            command.find_subcommand_mut(&mode_name).unwrap().error(ErrorKind::ArgumentConflict, problem).exit();
+        }
+    }
+    if matches!(args.mode, Mode::Hunt) {
+        let config = z3::Config::new();
+        let ctx = z3::Context::new(&config);
+        solve_linked(&file_path, &ctx);
+        return;
+    }
+    let mut sudoku = open_sudoku(&file_path);
+    for restriction in &args.restrict {
+        match parse_restriction(restriction, sudoku.size()) {
+            Some(region) => sudoku.digit_regions.push(region),
+            None => {
+                println!("Restrictions look like r5c5=2,4,6, could not read {restriction}.");
+                return;
+            }
+        }
+    }
+    sudoku.prune = matches!(args.mode, Mode::Solution { prune: true, .. } | Mode::Count { prune: true, .. });
+    let ndjson = matches!(args.mode, Mode::Solution { format: Format::Ndjson, .. } | Mode::Count { format: Format::Ndjson, .. });
+    let writes_puzzle = matches!(args.mode, Mode::Normalize { .. } | Mode::ExportSmt { .. } | Mode::Relabel { .. } | Mode::Fingerprint { .. });
+    let seed = args.seed.unwrap_or_else(rng::random_seed);
+    let metadata = run_metadata(&args, &mode_name, seed);
+    if ndjson {
+        println!("{}", json!({"run": metadata}));
+    } else if args.verbose && !writes_puzzle {
+        let fields = metadata.as_object().unwrap().iter().map(|(key, value)| match value.as_str() {
+            Some(text) => format!("{key} {text}"),
+            None => format!("{key} {value}"),
+        }).collect::<Vec<_>>();
+        println!("Run: {}", fields.join(", "));
+    }
+    let timer = RunTimer {
+        start: Instant::now(),
+        ndjson,
+        verbose: args.verbose,
+        notify: args.notify.clone().map(|url| (url, metadata.clone())),
+        result: None,
+    };
+    if !writes_puzzle && !ndjson {
+        for group in &sudoku.offset_groups {
+            println!("Applying offset group {} ({} offsets)", group.name, group.offsets.len());
+        }
+        for gap in line_gaps(&sudoku, args.adjacency) {
+            println!("Warning: {gap}");
+        }
+        if sudoku.prune {
+            println!("{}", pruning_summary(&sudoku));
+        }
+        if args.verbose {
+            for line in describe(&sudoku) {
+                println!("{line}");
             }
-            let solver = Solver::new(&ctx);
-            add_solver_constraints(&sudoku, &grid, &solver, &ctx);
-            println!("Constraints added. Counting solutions...");
-            for num in 0..args.max_sudoku {
+        }
+    }
+
+    #[cfg(feature = "sat")]
+    if args.backend == Backend::Sat {
+        let mut timer = timer;
+        solve_with_sat(&sudoku, &args.mode, ndjson, &mut timer);
+        return;
+    }
+
+    let config = z3::Config::new();
+    let ctx = &z3::Context::new(&config);
+    std::thread::scope(|scope| {
+        let mut timer = timer;
+        let cancel = interruptible(ctx, scope);
+
+        let size = sudoku.size();
+        let grid = new_grid(ctx, size);
+
+        match args.mode {
+            Mode::Hunt | Mode::Verify | Mode::Completions { .. } => unreachable!("{mode_name} returns before the sudoku is solved"),
+            Mode::Solution { .. } => {
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                if ndjson {
+                    match solver.check() {
+                        SatResult::Sat => println!("{}", solution_json(&sudoku, &solver.get_model().unwrap(), &grid, ctx)),
+                        SatResult::Unsat => println!("{}", json!({"solution": null})),
+                        SatResult::Unknown => panic!("Solver returned unknown!"),
+                    }
+                    return;
+                }
+                println!("Constraints added. Solver is running...");
                 match solver.check() {
                     SatResult::Sat => {
+                        println!("Possible solution found!");
                         let model = solver.get_model().unwrap();
-                        let mut filled_sudoku = [[0; 9]; 9];
-                        for i in 0..9 {
-                            for j in 0..9 {
-                                filled_sudoku[i][j] = model.get_const_interp(&grid[i][j]).unwrap().as_u64().unwrap();
-                            }
-                        }
-                        let a = grid.iter().enumerate().flat_map(
-                            |(i, x)| x.iter().enumerate().map(
-                                |(j, y)| Bool::not(&y._eq(&Int::from_u64(&ctx, filled_sudoku[i][j])))
-                            ).collect::<Vec<_>>()
-                        ).collect::<Vec<_>>();
-                        solver.assert(&Bool::or(&ctx, &a.iter().map(|x| x).collect::<Vec<_>>()[..]));
-                    }
+                        print_sudoku_from_model(&sudoku, &model, &grid);
+                        print_unknowns(&sudoku, &model, ctx);
+                    },
                     SatResult::Unsat => {
-                        println!("Found {num} possible sudokus!");
+                        println!("Could not find a satisfying Sudoku.");
+                    },
+                    SatResult::Unknown => {
+                        panic!("Solver returned unknown!");
+                    }
+                }
+            },
+            Mode::Count { max_sudoku, count_by, split, .. } => {
+                if !split.is_empty() {
+                    let Some(cells) = split.iter().map(|cell| parse_cell(cell, size)).collect::<Option<Vec<_>>>() else {
+                        println!("Could not read the split squares, squares look like r0c0.");
                         return;
+                    };
+                    if !ndjson {
+                        println!("Counting solutions in parallel, split on {}...", split.join(", "));
                     }
-                    SatResult::Unknown => {
-                        println!("Unknown reached? Stopping...");
+                    let count = count_parallel(&sudoku, &cells, max_sudoku);
+                    timer.result = Some(format!("{}{} solutions", if count > max_sudoku { ">" } else { "" }, count.min(max_sudoku)));
+                    if ndjson {
+                        println!("{}", json!({"count": count.min(max_sudoku), "capped": count > max_sudoku}));
+                    } else if count > max_sudoku {
+                        println!("Found >{} possible sudokus!", max_sudoku);
+                    } else {
+                        println!("Found {count} possible sudokus!");
+                    }
+                    return;
+                }
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                if let Some(cell) = &count_by {
+                    let Some((row, col)) = parse_cell(cell, size) else {
+                        println!("Could not read square {cell}, squares look like r1c1.");
                         return;
+                    };
+                    if !ndjson {
+                        println!("Constraints added. Counting solutions for each value of r{row}c{col}...");
+                    }
+                    let mut total = 0;
+                    let mut capped = false;
+                    for &digit in &sudoku.digits {
+                        solver.push();
+                        solver.assert(&grid[row][col]._eq(&Int::from_u64(ctx, digit)));
+                        let count = count_up_to(&grid, &solver, ctx, max_sudoku + 1);
+                        solver.pop(1);
+                        if interrupted() {
+                            let found = total + count.min(max_sudoku);
+                            if ndjson {
+                                println!("{}", json!({"count": found, "capped": capped, "interrupted": true}));
+                            } else {
+                                println!("Interrupted while counting r{row}c{col} = {digit}, found at least {found} possible sudokus.");
+                            }
+                            timer.result = Some(format!("interrupted, at least {found} solutions"));
+                            drop(timer);
+                            std::process::exit(EXIT_INTERRUPTED);
+                        }
+                        if ndjson {
+                            println!("{}", json!({"square": cell, "digit": digit, "count": count.min(max_sudoku), "capped": count > max_sudoku}));
+                        } else if count > max_sudoku {
+                            println!("r{row}c{col} = {digit}: >{} possible sudokus", max_sudoku);
+                        } else {
+                            println!("r{row}c{col} = {digit}: {count} possible sudokus");
+                        }
+                        capped |= count > max_sudoku;
+                        total += count.min(max_sudoku);
                     }
+                    if ndjson {
+                        println!("{}", json!({"count": total, "capped": capped}));
+                    } else {
+                        println!("Found {}{total} possible sudokus!", if capped { ">" } else { "" });
+                    }
+                    timer.result = Some(format!("{}{total} solutions", if capped { ">" } else { "" }));
+                    return;
                 }
-            }
-            println!("Found >{} possible sudokus!", args.max_sudoku);
-        },
-        Mode::Hint => {
-            if args.row.is_some() || args.col.is_some() {
-                println!("Ignoring row and column information in Solution mode.");
-            }
-            let optimizer = Optimize::new(&ctx);
-            add_optimizer_constraints(&sudoku, &grid, &optimizer, &ctx);
-            let mut clues = [[[false; 9]; 9]; 9];
-            println!("Constraints added. Finding all possible values of every square...");
-            for num in 1..=args.max_sudoku {
-                match optimizer.check(&[]) {
-                    SatResult::Sat => {
-                        let model = optimizer.get_model().unwrap();
-                        let mut answer = [[0; 9]; 9];
-                        let mut new_info = 0;
-                        for i in 0..9 {
-                            for j in 0..9 {
-                                answer[i][j] = model.get_const_interp(&grid[i][j]).unwrap().as_u64().unwrap();
-                                if !clues[i][j][(answer[i][j] - 1) as usize] {
-                                    new_info += 1;
-                                    clues[i][j][(answer[i][j] - 1) as usize] = true;
-                                }
+                if !ndjson {
+                    println!("Constraints added. Counting solutions...");
+                }
+                for num in 0..max_sudoku {
+                    match solver.check() {
+                        SatResult::Sat => {
+                            let model = solver.get_model().unwrap();
+                            if ndjson {
+                                println!("{}", solution_json(&sudoku, &model, &grid, ctx));
+                            }
+                            block_solution(&model, &grid, &solver, ctx);
+                        }
+                        SatResult::Unsat => {
+                            if ndjson {
+                                println!("{}", json!({"count": num, "capped": false}));
+                            } else {
+                                println!("Found {num} possible sudokus!");
                             }
+                            timer.result = Some(format!("{num} solutions"));
+                            return;
+                        }
+                        SatResult::Unknown if interrupted_query(solver.get_reason_unknown()) => {
+                            if ndjson {
+                                println!("{}", json!({"count": num, "capped": false, "interrupted": true}));
+                            } else {
+                                println!("Interrupted, found at least {num} possible sudokus.");
+                            }
+                            timer.result = Some(format!("interrupted, at least {num} solutions"));
+                            drop(timer);
+                            std::process::exit(EXIT_INTERRUPTED);
+                        }
+                        SatResult::Unknown => {
+                            println!("Unknown reached? Stopping...");
+                            return;
                         }
-                        println!("Iteration {num}: Found {new_info} new clues");
-                        if new_info == 0 {
-                            for i in 0..9 {
-                                for j in 0..9 {
-                                    print!("Row {i} Column {j}: ");
-                                    for k in 0..9 {
-                                        if clues[i][j][k] {
-                                            print!("{} ", k + 1);
-                                        }
+                    }
+                }
+                if ndjson {
+                    println!("{}", json!({"count": max_sudoku, "capped": true}));
+                } else {
+                    println!("Found >{} possible sudokus!", max_sudoku);
+                }
+                timer.result = Some(format!(">{} solutions", max_sudoku));
+            },
+            Mode::Hint { max_sudoku, stop_when_unique, budget: budget_seconds, heatmap, output } => {
+                let optimizer = Optimize::new(ctx);
+                add_optimizer_constraints(&sudoku, &grid, &optimizer, ctx);
+                let mut clues = vec![vec![Vec::new(); size]; size];
+                let budget = budget_seconds.map(|seconds| {
+                    let mut bounds = Candidates::new(&sudoku);
+                    bounds.solve();
+                    cancel.cancel_after(Duration::from_secs(seconds));
+                    bounds.grid
+                });
+                let expired = || budget.as_ref().filter(|_| cancel.is_cancelled() && !interrupted());
+                let ranges = range_hints(&sudoku);
+                if !ranges.is_empty() {
+                    println!("Range hints:");
+                    for range in ranges {
+                        println!("  {range}");
+                    }
+                }
+                println!("Constraints added. Finding all possible values of every square...");
+                for num in 1..=max_sudoku {
+                    let result = if expired().is_some() { SatResult::Unknown } else { optimizer.check(&[]) };
+                    match result {
+                        SatResult::Sat => {
+                            let model = optimizer.get_model().unwrap();
+                            let answer = solution_from_model(&model, &grid);
+                            let mut new_info = 0;
+                            for i in 0..size {
+                                for j in 0..size {
+                                    if !clues[i][j].contains(&answer[i][j]) {
+                                        new_info += 1;
+                                        clues[i][j].push(answer[i][j]);
                                     }
-                                    println!();
                                 }
                             }
+                            println!("Iteration {num}: Found {new_info} new clues");
+                            if new_info == 0 {
+                                print_clues(&clues);
+                                show_heatmap(&clues, sudoku.digits.len(), heatmap, output.as_deref());
+                                timer.result = Some(format!("all hints after {num} iterations"));
+                                return;
+                            }
+                            if stop_when_unique && clues.iter().flatten().all(|square| square.len() == 1) {
+                                let others = grid.iter().flatten().zip(answer.iter().flatten()).map(
+                                    |(square, &digit)| Bool::not(&square._eq(&Int::from_u64(ctx, digit)))
+                                ).collect::<Vec<_>>();
+                                optimizer.push();
+                                optimizer.assert(&Bool::or(ctx, &others.iter().collect::<Vec<_>>()));
+                                let unique = optimizer.check(&[]) == SatResult::Unsat;
+                                optimizer.pop();
+                                if unique {
+                                    println!("Every square has exactly one possible value, the solution is unique.");
+                                    print_clues(&clues);
+                                    show_heatmap(&clues, sudoku.digits.len(), heatmap, output.as_deref());
+                                    timer.result = Some("unique".to_string());
+                                    return;
+                                }
+                            }
+                            for i in 0..size {
+                                for j in 0..size {
+                                    optimizer.assert_soft(&Bool::not(&grid[i][j]._eq(&Int::from_u64(ctx, answer[i][j]))), 1, None);
+                                }
+                            }
+                        }
+                        SatResult::Unsat => {
+                            println!("Could not find a satisfying sudoku.");
+                            timer.result = Some("unsat".to_string());
                             return;
                         }
-                        for i in 0..9 {
-                            for j in 0..9 {
-                                optimizer.assert_soft(&Bool::not(&grid[i][j]._eq(&Int::from_u64(&ctx, answer[i][j]))), 1, None);
-                            }
+                        SatResult::Unknown if expired().is_some() => {
+                            let bounds = expired().unwrap();
+                            println!("Budget of {}s reached after {} iterations.", budget_seconds.unwrap(), num - 1);
+                            print_budgeted_clues(bounds, &clues);
+                            show_heatmap(bounds, sudoku.digits.len(), heatmap, output.as_deref());
+                            timer.result = Some(format!("budget reached after {} iterations", num - 1));
+                            return;
                         }
+                        SatResult::Unknown if interrupted_query(optimizer.get_reason_unknown()) => {
+                            println!("Interrupted after {} iterations. Known hints found so far:", num - 1);
+                            print_clues(&clues);
+                            show_heatmap(&clues, sudoku.digits.len(), heatmap, output.as_deref());
+                            timer.result = Some(format!("interrupted after {} iterations", num - 1));
+                            drop(timer);
+                            std::process::exit(EXIT_INTERRUPTED);
+                        }
+                        SatResult::Unknown => {
+                            println!("Unknown reached? Stopping...");
+                            timer.result = Some("unknown".to_string());
+                            return;
+                        }
+                    }
+                }
+                println!("Reached maximum iterations ({}). Try adding more constraints or increase max_sudoku.", max_sudoku);
+                println!("Known hints found so far:");
+                print_clues(&clues);
+                show_heatmap(&clues, sudoku.digits.len(), heatmap, output.as_deref());
+                timer.result = Some(format!("maximum of {max_sudoku} iterations reached"));
+            },
+            Mode::Square { row, col } => {
+                if size <= row || size <= col {
+                    println!("Invalid square, {} {}", row, col);
+                    return;
+                }
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                println!("Constraints added. Finding possible values...");
+                let mut candidates = sudoku.given.iter().map(
+                    |row| row.iter().map(|given| given.iter().copied().collect::<Vec<_>>()).collect::<Vec<_>>()
+                ).collect::<Vec<_>>();
+                candidates[row][col].clear();
+                for &i in &sudoku.digits {
+                    solver.push();
+                    solver.assert(&grid[row][col]._eq(&Int::from_u64(ctx, i)));
+                    match solver.check() {
+                        SatResult::Sat => candidates[row][col].push(i),
+                        SatResult::Unsat => {},
+                        SatResult::Unknown => println!("Unknown reached while checking {i}!"),
                     }
+                    solver.pop(1);
+                }
+                print_candidate_grid(&candidates, &[(row, col)]);
+                print!("Row {row} Column {col}: ");
+                for i in &candidates[row][col] {
+                    print!("{i} ");
+                }
+                println!();
+            },
+            Mode::Estimate { epsilon, delta } => {
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                println!("Constraints added. Estimating number of solutions...");
+                estimate_solutions(&sudoku, &grid, &solver, ctx, epsilon, delta, seed);
+            },
+            Mode::Sample { samples } => {
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                println!("Constraints added. Sampling solutions...");
+                sample_solutions(&sudoku, &grid, &solver, ctx, samples, seed);
+            },
+            Mode::Profile { trials } => {
+                println!("Timing each constraint family over {} runs...", trials);
+                profile_families(&sudoku, &grid, ctx, trials);
+            },
+            Mode::ExportSmt { output } => {
+                let session = Session::new(&sudoku, ctx);
+                let smt = format!("{}(check-sat)\n(get-model)\n", session.solver);
+                match output {
+                    Some(path) => std::fs::write(path, smt).unwrap(),
+                    None => print!("{smt}"),
+                }
+            },
+            Mode::Orient => {
+                println!("Trying every orientation of the thermos and arrows...");
+                orient_lines(&sudoku, &grid, ctx);
+            },
+            Mode::Normalize { output } => {
+                let errors = validate(&sudoku, args.adjacency);
+                if !errors.is_empty() {
+                    for error in errors {
+                        eprintln!("{error}");
+                    }
+                    return;
+                }
+                let json = serde_json::to_string_pretty(&sudoku_to_json(&normalize(&sudoku))).unwrap();
+                match output {
+                    Some(path) => std::fs::write(path, json + "\n").unwrap(),
+                    None => println!("{json}"),
+                }
+            },
+            Mode::Relabel { output } => {
+                match relabel(&sudoku, seed) {
+                    Ok((relabelled, changes)) => {
+                        for change in changes {
+                            eprintln!("{change}");
+                        }
+                        let json = serde_json::to_string_pretty(&sudoku_to_json(&normalize(&relabelled))).unwrap();
+                        match output {
+                            Some(path) => std::fs::write(path, json + "\n").unwrap(),
+                            None => println!("{json}"),
+                        }
+                    }
+                    Err(reason) => eprintln!("Cannot relabel the sudoku: {reason}."),
+                }
+            },
+            Mode::Fingerprint { archive } => {
+                println!("Fingerprint: {}", fingerprint(&sudoku));
+                if let Some(archive) = &archive {
+                    let own = std::fs::canonicalize(&file_path).ok();
+                    let mut paths = std::fs::read_dir(archive).unwrap()
+                        .map(|entry| entry.unwrap().path())
+                        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+                        .filter(|path| std::fs::canonicalize(path).ok() != own)
+                        .collect::<Vec<_>>();
+                    paths.sort();
+                    let canonical = canonical_form(&sudoku);
+                    let matches = paths.iter()
+                        .filter(|path| canonical_form(&open_sudoku(&path.display().to_string())) == canonical)
+                        .collect::<Vec<_>>();
+                    if matches.is_empty() {
+                        println!("No puzzle among the {} in {archive} is the same up to relabelling.", paths.len());
+                    }
+                    for path in matches {
+                        println!("Same puzzle up to relabelling as {}", path.display());
+                    }
+                }
+            },
+            Mode::Analyze => {
+                for line in given_distribution(&sudoku) {
+                    println!("{line}");
+                }
+            },
+            Mode::Clues { border, output } => {
+                if let Some((side, index)) = border.iter().find(|(_, index)| *index >= size) {
+                    let mut command = Args::command();
+                    command.build();
+                    let mode = command.find_subcommand_mut(&mode_name).unwrap();
+                    let message = format!("invalid value '{}:{index}' for '--border <BORDER>': the grid only has {size} rows and columns", side.name());
+                    mode.error(ErrorKind::ValueValidation, message).exit();
+                }
+                let borders = if border.is_empty() {
+                    [Side::Left, Side::Right, Side::Top, Side::Bottom].iter().flat_map(|&side| (0..size).map(move |index| (side, index))).collect()
+                } else {
+                    border
+                };
+                let solver = Solver::new(ctx);
+                add_solver_constraints(&sudoku, &grid, &solver, ctx);
+                println!("Constraints added. Solver is running...");
+                match solver.check() {
+                    SatResult::Sat => {
+                        let solution = solution_from_model(&solver.get_model().unwrap(), &grid);
+                        fill_border_clues(&sudoku, &solution, &borders, output);
+                    },
                     SatResult::Unsat => {
-                        println!("Could not find a satisfying sudoku.");
-                        return;
+                        println!("Could not find a satisfying Sudoku.");
+                    },
+                    SatResult::Unknown => {
+                        panic!("Solver returned unknown!");
                     }
+                }
+            },
+            Mode::CageSums { solution, minimize, output } => {
+                if sudoku.killer_cages.is_empty() {
+                    println!("The sudoku has no killer cages.");
+                    return;
+                }
+                let solution = match &solution {
+                    Some(path) => {
+                        let v: Value = serde_json::from_reader(BufReader::new(File::open(path).unwrap())).unwrap();
+                        let grid = if v["given"].is_null() { v } else { v["given"].clone() };
+                        serde_json::from_value::<Vec<Vec<u64>>>(grid).unwrap_or_else(|_| panic!("{path} is not a solved grid"))
+                    },
+                    None => {
+                        println!("Constraints added. Solver is running...");
+                        match Session::new(&sudoku, ctx).solve() {
+                            Some(solution) => solution,
+                            None => {
+                                println!("Could not find a satisfying Sudoku.");
+                                return;
+                            },
+                        }
+                    },
+                };
+                if solution.len() != size || solution.iter().any(|row| row.len() != size) {
+                    println!("The solution must be a {size}x{size} grid.");
+                    return;
+                }
+                let needed = if minimize { needed_cage_sums(&sudoku, &solution, ctx) } else { None };
+                if minimize && needed.is_none() {
+                    println!("The solution is not unique even with every cage sum, so every sum is kept.");
+                }
+                fill_cage_sums(&sudoku, &solution, needed.as_deref(), output);
+            },
+            Mode::Repair => {
+                let optimizer = Optimize::new(ctx);
+                add_optimizer_constraints(&sudoku, &grid, &optimizer, ctx);
+                let soft_asts = sudoku.soft.iter().map(|soft| soft_constraint_ast(&sudoku, &grid, soft, ctx)).collect::<Vec<_>>();
+                for (soft, ast) in sudoku.soft.iter().zip(&soft_asts) {
+                    optimizer.assert_soft(ast, soft.weight, None);
+                }
+                println!("Constraints added. Finding the least broken solution...");
+                match optimizer.check(&[]) {
+                    SatResult::Sat => {
+                        let model = optimizer.get_model().unwrap();
+                        let mut broken_weight = 0;
+                        for (i, (soft, ast)) in sudoku.soft.iter().zip(&soft_asts).enumerate() {
+                            if !model.eval(ast, true).unwrap().as_bool().unwrap() {
+                                println!("Broken: soft constraint {i} ({}, weight {}) on {:?}", soft.rule.name(), soft.weight, soft.cells);
+                                broken_weight += soft.weight;
+                            }
+                        }
+                        println!("Total broken weight: {broken_weight}");
+                        print_sudoku_from_model(&sudoku, &model, &grid);
+                        print_unknowns(&sudoku, &model, ctx);
+                    },
+                    SatResult::Unsat => {
+                        println!("Could not find a satisfying Sudoku, the hard constraints conflict.");
+                    },
                     SatResult::Unknown => {
-                        println!("Unknown reached? Stopping...");
+                        panic!("Solver returned unknown!");
+                    }
+                }
+            },
+            Mode::Objective { maximize, minimize } => {
+                let (expression, maximize) = match (&maximize, &minimize) {
+                    (Some(expression), _) => (expression, true),
+                    (_, Some(expression)) => (expression, false),
+                    (None, None) => unreachable!("clap requires --maximize or --minimize"),
+                };
+                let objective = match parse_linear(expression, &grid, ctx) {
+                    Ok(objective) => objective,
+                    Err(error) => {
+                        println!("{error}");
                         return;
                     }
+                };
+                let optimizer = Optimize::new(ctx);
+                add_optimizer_constraints(&sudoku, &grid, &optimizer, ctx);
+                if maximize {
+                    optimizer.maximize(&objective);
+                } else {
+                    optimizer.minimize(&objective);
                 }
-            }
-            println!("Reached maximum iterations ({}). Try adding more constraints or increase max_sudoku.", args.max_sudoku);
-            println!("Known hints found so far:");
-            for i in 0..9 {
-                for j in 0..9 {
-                    print!("Row {i} Column {j}: ");
-                    for k in 0..9 {
-                        if clues[i][j][k] {
-                            print!("{} ", k + 1);
+                println!("Constraints added. Optimizing objective...");
+                match optimizer.check(&[]) {
+                    SatResult::Sat => {
+                        let model = optimizer.get_model().unwrap();
+                        println!("Optimal objective value: {}", model.eval(&objective, true).unwrap());
+                        print_sudoku_from_model(&sudoku, &model, &grid);
+                        print_unknowns(&sudoku, &model, ctx);
+                    },
+                    SatResult::Unsat => {
+                        println!("Could not find a satisfying Sudoku.");
+                    },
+                    SatResult::Unknown => {
+                        panic!("Solver returned unknown!");
+                    }
+                }
+            },
+            Mode::Placement { max_sudoku } => {
+                println!("Searching for placements of the unknown givens...");
+                place_unknown_givens(&sudoku, &grid, ctx, max_sudoku);
+            },
+            Mode::Fog => {
+                println!("Solving through the fog...");
+                simulate_fog(&sudoku, &grid, ctx);
+            },
+            Mode::Delta { compare } => {
+                let edited = open_sudoku(&compare);
+                if edited.size() != size {
+                    println!("Cannot compare a {size}x{size} sudoku with a {0}x{0} one.", edited.size());
+                    return;
+                }
+                println!("Finding the candidates of both versions...");
+                candidate_delta(&sudoku, &edited, ctx);
+            },
+            Mode::Logic { heatmap, output } => {
+                let session = Session::new(&sudoku, ctx);
+                let start = Candidates::new(&sudoku);
+                let mut candidates = start.clone();
+                println!("Applying logical techniques...");
+                let mut steps = Vec::new();
+                loop {
+                    for step in candidates.solve() {
+                        println!("Step {}: {}", steps.len() + 1, step.explain());
+                        steps.push(step);
+                    }
+                    if candidates.contradiction() || candidates.solved() {
+                        break;
+                    }
+                    let Some(chain) = find_chain(&candidates, &session) else {
+                        break;
+                    };
+                    println!("Step {}: {}", steps.len() + 1, chain.step().explain());
+                    for line in chain.explain() {
+                        println!("    {line}");
+                    }
+                    candidates.apply(&chain.step());
+                    steps.push(chain.step());
+                }
+                if candidates.contradiction() {
+                    println!("A square ran out of candidates, the sudoku has no solution.");
+                } else if candidates.solved() {
+                    println!("Solved with logic alone in {} steps.", steps.len());
+                    if let Some(hardest) = steps.iter().map(|step| step.technique).max() {
+                        let needed = start.needed_steps(&steps);
+                        let placements = needed.iter().filter(|&&index| steps[index].placement.is_some()).count();
+                        println!("Hardest technique: {}.", hardest.name());
+                        println!(
+                            "Shortest path found: {} steps, {placements} placements and {} eliminations.",
+                            needed.len(), needed.len() - placements,
+                        );
+                    }
+                } else {
+                    println!("Stuck after {} steps.", steps.len());
+                }
+                print_candidate_grid(&candidates.grid, &[]);
+                show_heatmap(&candidates.grid, sudoku.digits.len(), heatmap, output.as_deref());
+            },
+            Mode::Mistakes { solution } => {
+                let solution = match &solution {
+                    Some(path) => {
+                        let v: Value = serde_json::from_reader(BufReader::new(File::open(path).unwrap())).unwrap();
+                        let grid = if v["given"].is_null() { v } else { v["given"].clone() };
+                        serde_json::from_value::<Vec<Vec<u64>>>(grid).unwrap_or_else(|_| panic!("{path} is not a solved grid"))
+                    },
+                    None => {
+                        let puzzle = Sudoku { state: State::default(), ..sudoku.clone() };
+                        let session = Session::new(&puzzle, ctx);
+                        println!("Constraints added. Finding the intended solution...");
+                        let count = session.count(2);
+                        if count != 1 {
+                            println!("The sudoku has {} solutions without the progress, so there is no intended solution to check against.", if count == 0 { "no" } else { "several" });
+                            return;
                         }
+                        session.solve().unwrap()
+                    },
+                };
+                if solution.len() != size || solution.iter().any(|row| row.len() != size) {
+                    println!("The solution must be a {size}x{size} grid.");
+                    return;
+                }
+                check_progress(&sudoku, &solution);
+            },
+            Mode::Project { max_sudoku, project: expressions } => {
+                let session = Session::new(&sudoku, ctx);
+                println!("Constraints added. Projecting solutions...");
+                for expression in &expressions {
+                    let projection = match parse_projection(expression, &sudoku, &grid, ctx) {
+                        Ok(projection) => projection,
+                        Err(error) => {
+                            println!("{error}");
+                            continue;
+                        }
+                    };
+                    let values = project(&session, &projection, max_sudoku);
+                    let listed = values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ");
+                    match values.len() {
+                        0 => println!("{expression}: the sudoku has no solution"),
+                        1 => println!("{expression} = {listed} in every solution"),
+                        n if n as u32 >= max_sudoku => println!("{expression} takes at least {n} values: {listed}"),
+                        n => println!("{expression} takes {n} values: {listed}"),
                     }
-                    println!();
                 }
-            }
-        },
-        Mode::Square => {
-            if args.row.is_none() || args.col.is_none() {
-                println!("Please specify the row and column of the square.");
-                return;
-            }
-            let row = args.row.unwrap();
-            let col = args.col.unwrap();
-            if 9 <= row || 9 <= col {
-                println!("Invalid square, {} {}", row, col);
-                return;
-            }
-            let solver = Solver::new(&ctx);
-            add_solver_constraints(&sudoku, &grid, &solver, &ctx);
-            println!("Constraints added. Finding possible values...");
-            for i in 1..=9 {
-                println!("Checking {}...", i);
-                solver.push();
-                solver.assert(&grid[row][col]._eq(&Int::from_u64(&ctx, i)));
-                match solver.check() {
-                    SatResult::Sat => println!("True!"),
-                    SatResult::Unsat => println!("False!"),
-                    SatResult::Unknown => println!("Unknown!"),
+            },
+            Mode::Sudokupad => {
+                let session = Session::new(&sudoku, ctx);
+                println!("Constraints added. Finding the candidates of every square...");
+                if session.solve().is_none() {
+                    println!("The sudoku has no solution with the progress so far.");
+                    return;
                 }
-                solver.pop(1);
-            }
+                println!("{}", progress_url(&sudoku, &session.candidates()));
+            },
+            Mode::Next { reveal } => {
+                let session = Session::new(&sudoku, ctx);
+                println!("Constraints added. Looking for the easiest square...");
+                if session.solve().is_none() {
+                    println!("The sudoku has no solution with the progress so far.");
+                    return;
+                }
+                let Some(next) = next_safe_digit(&sudoku, &session) else {
+                    let filled = (0..size).all(|i| (0..size).all(|j| sudoku.given[i][j].or(sudoku.state.filled[i][j]).is_some()));
+                    if filled {
+                        println!("Every square is already filled.");
+                    } else {
+                        println!("No square is forced yet, more than one digit fits everywhere.");
+                    }
+                    return;
+                };
+                let (i, j) = next.cell;
+                println!("The easiest square to fill next is r{i}c{j}, by {}.", next.reason);
+                if reveal {
+                    println!("It holds {}.", next.digit);
+                } else {
+                    println!("Run again with --reveal to see its digit.");
+                }
+            },
         }
-    }
+    });
 }
+
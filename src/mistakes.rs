@@ -0,0 +1,49 @@
+use crate::{print_candidate_grid, Sudoku};
+
+/// Compares the filled digits and pencilmarks of the state with the intended solution, printing every mistake
+pub fn check_progress(sudoku: &Sudoku, solution: &[Vec<u64>]) {
+    let mut right = 0;
+    let mut wrong = Vec::new();
+    for (i, row) in sudoku.state.filled.iter().enumerate() {
+        for (j, filled) in row.iter().enumerate() {
+            let Some(digit) = *filled else {
+                continue;
+            };
+            if digit == solution[i][j] {
+                right += 1;
+            } else {
+                println!("Wrong digit at r{i}c{j}: {digit} should be {}", solution[i][j]);
+                wrong.push((i, j));
+            }
+        }
+    }
+
+    let mut unsound = Vec::new();
+    for mark in &sudoku.state.pencilmarks {
+        let (i, j) = (mark.cell[0], mark.cell[1]);
+        if !mark.digits.contains(&solution[i][j]) {
+            println!("Unsound pencilmarks at r{i}c{j}: {} was eliminated", solution[i][j]);
+            unsound.push((i, j));
+        }
+    }
+
+    println!(
+        "{right} filled digits right, {} wrong, {} of {} pencilmarked squares unsound.",
+        wrong.len(), unsound.len(), sudoku.state.pencilmarks.len(),
+    );
+    if wrong.is_empty() && unsound.is_empty() {
+        println!("No mistakes so far.");
+        return;
+    }
+    let mut progress = sudoku.given.iter().zip(&sudoku.state.filled).map(|(given_row, filled_row)| {
+        given_row.iter().zip(filled_row).map(|(given, filled)| given.or(*filled).into_iter().collect::<Vec<_>>()).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    for mark in &sudoku.state.pencilmarks {
+        let square = &mut progress[mark.cell[0]][mark.cell[1]];
+        if square.is_empty() {
+            square.clone_from(&mark.digits);
+        }
+    }
+    wrong.extend(unsound);
+    print_candidate_grid(&progress, &wrong);
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use serde_json::Value;
+use z3::{Context, SatResult, Solver};
+use z3::ast::{Ast, Int};
+
+use crate::{block_solution, load_sudoku_json, new_named_grid, parse_cell, parse_sudoku, print_sudoku_from_model, print_unknowns, Clue, Sudoku};
+use crate::solver::add_solver_constraints;
+
+enum LinkRule {
+    Equal,
+    Distinct,
+    Sum(u64),
+}
+
+/// A constraint between squares of several grids, each square written as grid:rXcY
+struct Link {
+    cells: Vec<(usize, usize, usize)>,
+    rule: LinkRule,
+}
+
+/// A puzzle of the hunt, its unknowns renamed with the grid's prefix so they are not shared with other grids
+fn load_grid(name: &str, path: &Path) -> Sudoku {
+    let mut sudoku = parse_sudoku(&load_sudoku_json(path, &mut Vec::new()));
+    sudoku.prefix = format!("{name}.");
+    for unknown in &mut sudoku.unknowns {
+        unknown.name = format!("{name}.{}", unknown.name);
+    }
+    for clue in sudoku.clues_mut() {
+        if let Clue::Unknown(unknown) = clue {
+            *unknown = format!("{name}.{unknown}");
+        }
+    }
+    sudoku
+}
+
+fn parse_link(link: &Value, names: &[String], grids: &[Sudoku]) -> Link {
+    let cells = link["cells"].as_array().unwrap_or_else(|| panic!("Link {link} has no cells")).iter().map(|cell| {
+        let cell = cell.as_str().unwrap_or_else(|| panic!("Invalid linked square {cell}"));
+        let (name, square) = cell.split_once(':').unwrap_or_else(|| panic!("Linked square {cell} must look like A:r0c0"));
+        let index = names.iter().position(|known| known == name).unwrap_or_else(|| panic!("Unknown grid {name}"));
+        let (i, j) = parse_cell(square, grids[index].size()).unwrap_or_else(|| panic!("Invalid linked square {cell}"));
+        (index, i, j)
+    }).collect();
+    let rule = match link["rule"].as_str() {
+        None | Some("equal") => LinkRule::Equal,
+        Some("distinct") => LinkRule::Distinct,
+        Some("sum") => LinkRule::Sum(link["value"].as_u64().unwrap_or_else(|| panic!("Link {link} has no sum"))),
+        Some(rule) => panic!("Unknown link rule {rule}"),
+    };
+    Link { cells, rule }
+}
+
+/// Solves every grid of a hunt file together with the links between them, then checks the combined solution is unique
+pub fn solve_linked(fp: &str, ctx: &Context) {
+    let path = Path::new(fp);
+    let v = load_sudoku_json(path, &mut Vec::new());
+    let files = v["grids"].as_object().unwrap_or_else(|| panic!("{fp} has no grids"));
+    let names = files.keys().cloned().collect::<Vec<_>>();
+    let grids = files.iter().map(|(name, file)| {
+        let file = file.as_str().unwrap_or_else(|| panic!("Invalid grid file {file}"));
+        load_grid(name, &path.parent().unwrap().join(file))
+    }).collect::<Vec<_>>();
+    let links = v["links"].as_array().map(|links| links.iter().map(|link| parse_link(link, &names, &grids)).collect::<Vec<_>>()).unwrap_or_default();
+
+    let solver = Solver::new(ctx);
+    let squares = grids.iter().map(|sudoku| {
+        let squares = new_named_grid(ctx, sudoku.size(), &sudoku.prefix);
+        add_solver_constraints(sudoku, &squares, &solver, ctx);
+        squares
+    }).collect::<Vec<_>>();
+    for link in &links {
+        let linked = link.cells.iter().map(|&(grid, i, j)| &squares[grid][i][j]).collect::<Vec<_>>();
+        match link.rule {
+            LinkRule::Equal => {
+                for pair in linked.windows(2) {
+                    solver.assert(&pair[0]._eq(pair[1]));
+                }
+            }
+            LinkRule::Distinct => solver.assert(&Int::distinct(ctx, &linked)),
+            LinkRule::Sum(sum) => solver.assert(&Int::add(ctx, &linked)._eq(&Int::from_u64(ctx, sum))),
+        }
+    }
+    println!("Constraints added for {} grids and {} links. Solver is running...", grids.len(), links.len());
+
+    let model = match solver.check() {
+        SatResult::Sat => solver.get_model().unwrap(),
+        SatResult::Unsat => {
+            println!("The linked grids have no solution.");
+            return;
+        }
+        SatResult::Unknown => panic!("Solver returned unknown!"),
+    };
+    for ((name, sudoku), grid) in names.iter().zip(&grids).zip(&squares) {
+        println!("Grid {name}:");
+        print_sudoku_from_model(sudoku, &model, grid);
+        print_unknowns(sudoku, &model, ctx);
+    }
+    let every = squares.iter().flatten().flatten().cloned().collect::<Vec<_>>();
+    block_solution(&model, &[every], &solver, ctx);
+    match solver.check() {
+        SatResult::Unsat => println!("The linked solution is unique."),
+        SatResult::Sat => println!("The linked grids have more than one solution."),
+        SatResult::Unknown => panic!("Solver returned unknown!"),
+    }
+}
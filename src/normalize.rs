@@ -0,0 +1,571 @@
+use serde_json::{json, Value};
+
+use crate::{box_segments, box_shape, parse_cell, Adjacency, CageRelation, Clue, MaskRule, OffsetRelation, Reading, SoftRule, Sudoku};
+
+fn in_grid(cell: &[usize], size: usize) -> bool {
+    cell.len() == 2 && cell[0] < size && cell[1] < size
+}
+
+fn validate_lines(name: &str, lines: &[Vec<Vec<usize>>], size: usize, min_len: usize, max_len: usize, errors: &mut Vec<String>) {
+    for (i, line) in lines.iter().enumerate() {
+        if line.len() < min_len || line.len() > max_len {
+            errors.push(format!("{name} {i} has {} squares", line.len()));
+        }
+        if let Some(cell) = line.iter().find(|cell| !in_grid(cell, size)) {
+            errors.push(format!("{name} {i} contains invalid square {cell:?}"));
+        }
+    }
+}
+
+fn touching(a: &[usize], b: &[usize], adjacency: Adjacency) -> bool {
+    let (rows, cols) = (a[0].abs_diff(b[0]), a[1].abs_diff(b[1]));
+    match adjacency {
+        Adjacency::Orthogonal => rows + cols == 1,
+        Adjacency::Diagonal => rows.max(cols) == 1,
+    }
+}
+
+/// Consecutive squares of thermos, arrows and whispers that do not touch, which usually means a transcription error
+pub fn line_gaps(sudoku: &Sudoku, adjacency: Adjacency) -> Vec<String> {
+    let arrows = sudoku.arrow.iter().map(|arrow| arrow.cells.clone()).collect::<Vec<_>>();
+    let lines = [("Thermo", &sudoku.thermo), ("Arrow", &arrows), ("German whisper", &sudoku.german_whispers), ("Renban", &sudoku.renban),
+        ("Lockout line", &sudoku.lockout_lines), ("Entropic line", &sudoku.entropic_lines),
+        ("Modular line", &sudoku.modular_lines), ("Region sum line", &sudoku.region_sum_lines)];
+    let mut gaps = Vec::new();
+    for (name, lines) in lines {
+        for (i, line) in lines.iter().enumerate() {
+            for pair in line.windows(2) {
+                if pair.iter().all(|cell| cell.len() == 2) && !touching(&pair[0], &pair[1], adjacency) {
+                    gaps.push(format!("{name} {i} jumps from {:?} to {:?}", pair[0], pair[1]));
+                }
+            }
+        }
+    }
+    gaps
+}
+
+pub fn validate(sudoku: &Sudoku, adjacency: Adjacency) -> Vec<String> {
+    let mut errors = line_gaps(sudoku, adjacency);
+    let size = sudoku.size();
+    let cells = size * size;
+    if sudoku.digits.is_empty() {
+        errors.push("The sudoku has no digits".to_string());
+        return errors;
+    }
+    if sudoku.given.len() != size || sudoku.given.iter().any(|row| row.len() != size) {
+        errors.push(format!("Givens must be a {size}x{size} grid"));
+    }
+    validate_lines("Unknown givens", std::slice::from_ref(&sudoku.unknown_givens), size, 0, cells, &mut errors);
+    for offset in sudoku.offset.iter().chain(sudoku.offset_groups.iter().flat_map(|group| &group.offsets)) {
+        if offset.len() != 2 {
+            errors.push(format!("Offset {offset:?} must have a row and column"));
+        }
+    }
+    validate_lines("Thermo", &sudoku.thermo, size, 1, cells, &mut errors);
+    validate_lines("Arrow", &sudoku.arrow.iter().map(|arrow| arrow.cells.clone()).collect::<Vec<_>>(), size, 2, cells, &mut errors);
+    for (i, arrow) in sudoku.arrow.iter().enumerate() {
+        if arrow.pill == 0 || arrow.pill >= arrow.cells.len() {
+            errors.push(format!("Arrow {i} has a pill of {} squares", arrow.pill));
+        }
+    }
+    validate_lines("Adjacent kropki", &sudoku.kropki_adjacent, size, 2, 2, &mut errors);
+    validate_lines("Double kropki", &sudoku.kropki_double, size, 2, 2, &mut errors);
+    validate_lines("Ambiguous kropki", &sudoku.kropki_ambiguous, size, 2, 2, &mut errors);
+    for (i, bar) in sudoku.consecutive_bars.iter().flatten().enumerate() {
+        validate_lines("Consecutive bar", std::slice::from_ref(bar), size, 2, 2, &mut errors);
+        if bar.len() == 2 && bar[0].len() == 2 && bar[1].len() == 2 && bar[0][0].abs_diff(bar[1][0]) + bar[0][1].abs_diff(bar[1][1]) != 1 {
+            errors.push(format!("Consecutive bar {i} does not join adjacent squares"));
+        }
+    }
+    validate_lines("German whisper", &sudoku.german_whispers, size, 1, cells, &mut errors);
+    validate_lines("Renban", &sudoku.renban, size, 1, size, &mut errors);
+    validate_lines("Lockout line", &sudoku.lockout_lines, size, 2, cells, &mut errors);
+    validate_lines("Entropic line", &sudoku.entropic_lines, size, 3, cells, &mut errors);
+    validate_lines("Modular line", &sudoku.modular_lines, size, 3, cells, &mut errors);
+    validate_lines("Region sum line", &sudoku.region_sum_lines, size, 2, cells, &mut errors);
+    for (i, line) in sudoku.region_sum_lines.iter().enumerate() {
+        if line.len() >= 2 && line.iter().all(|cell| in_grid(cell, size)) && box_segments(line, size).len() < 2 {
+            errors.push(format!("Region sum line {i} stays in one box, so it has no sums to compare"));
+        }
+    }
+    if !sudoku.entropic_lines.is_empty() && !sudoku.digits.len().is_multiple_of(3) {
+        errors.push(format!("Entropic lines need the {} digits to split into three equal groups", sudoku.digits.len()));
+    }
+    for (i, soft) in sudoku.soft.iter().enumerate() {
+        let (min_len, max_len) = match soft.rule {
+            SoftRule::Given(_) => (1, 1),
+            SoftRule::Thermo | SoftRule::GermanWhisper => (1, cells),
+            SoftRule::Arrow => (2, cells),
+            SoftRule::KropkiAdjacent | SoftRule::KropkiDouble | SoftRule::KropkiAmbiguous => (2, 2),
+        };
+        validate_lines(&format!("Soft {}", soft.rule.name()), std::slice::from_ref(&soft.cells), size, min_len, max_len, &mut errors);
+        if matches!(soft.rule, SoftRule::Given(value) if !sudoku.digits.contains(&value)) {
+            errors.push(format!("Soft given {i} is not a digit"));
+        }
+    }
+    validate_lines("Fog", std::slice::from_ref(&sudoku.fog), size, 0, cells, &mut errors);
+    validate_lines("Odd shading", std::slice::from_ref(&sudoku.odd_shading), size, 0, cells, &mut errors);
+    validate_lines("Odd cells", std::slice::from_ref(&sudoku.odd_cells), size, 0, cells, &mut errors);
+    validate_lines("Even cells", std::slice::from_ref(&sudoku.even_cells), size, 0, cells, &mut errors);
+    for cell in sudoku.odd_cells.iter().filter(|cell| sudoku.even_cells.contains(cell)) {
+        errors.push(format!("Square {cell:?} is marked both odd and even"));
+    }
+    validate_lines("Quadruple", &sudoku.quadruples.iter().map(|quadruple| quadruple.cells()).collect::<Vec<_>>(), size, 4, 4, &mut errors);
+    for (i, quadruple) in sudoku.quadruples.iter().enumerate() {
+        if quadruple.digits.is_empty() || quadruple.digits.len() > 4 {
+            errors.push(format!("Quadruple {i} lists {} digits, it takes 1 to 4", quadruple.digits.len()));
+        }
+        if let Some(digit) = quadruple.digits.iter().find(|digit| !sudoku.digits.contains(digit)) {
+            errors.push(format!("Quadruple {i} lists {digit}, which is not a digit"));
+        }
+        if let Some(digit) = quadruple.digits.iter().find(|&&digit| quadruple.digits.iter().filter(|&&listed| listed == digit).count() > 2) {
+            errors.push(format!("Quadruple {i} lists {digit} more than twice, but the four squares hold it at most twice"));
+        }
+    }
+    if !sudoku.odd_shading.is_empty() && sudoku.odd_shading.iter().all(|cell| in_grid(cell, size)) {
+        let odd = sudoku.digits.iter().filter(|&&digit| digit % 2 == 1).count();
+        let (height, width) = box_shape(size);
+        let mut shaded = [vec![0; size], vec![0; size], vec![0; size]];
+        for cell in &sudoku.odd_shading {
+            shaded[0][cell[0]] += 1;
+            shaded[1][cell[1]] += 1;
+            shaded[2][cell[0] / height * (size / width) + cell[1] / width] += 1;
+        }
+        let units = [(sudoku.horizontal_rule, "Row"), (sudoku.vertical_rule, "Column"), (sudoku.nonet_rule, "Box")];
+        for ((rule, unit), counts) in units.iter().zip(&shaded) {
+            for (k, &count) in counts.iter().enumerate() {
+                if *rule && count != odd {
+                    errors.push(format!("{unit} {k} has {count} shaded squares but {odd} odd digits"));
+                }
+            }
+        }
+    }
+    let marked = sudoku.state.pencilmarks.iter().map(|mark| vec![mark.cell.clone()]).collect::<Vec<_>>();
+    validate_lines("Pencilmark", &marked, size, 1, 1, &mut errors);
+    for (i, mark) in sudoku.state.pencilmarks.iter().enumerate() {
+        if let Some(digit) = mark.digits.iter().find(|digit| !sudoku.digits.contains(digit)) {
+            errors.push(format!("Pencilmark {i} allows {digit}, which is not a digit"));
+        }
+    }
+    for (i, region) in sudoku.digit_regions.iter().enumerate() {
+        validate_lines("Digit region", std::slice::from_ref(&region.cells), size, 1, cells, &mut errors);
+        if let Some(digit) = region.digits.iter().find(|digit| !sudoku.digits.contains(digit)) {
+            errors.push(format!("Digit region {i} allows {digit}, which is not a digit"));
+        }
+    }
+    validate_lines("Killer cage", &sudoku.killer_cages.iter().map(|cage| cage.cells.clone()).collect::<Vec<_>>(), size, 1, size, &mut errors);
+    if let Some(modulo) = sudoku.cage_sum_modulo {
+        if modulo == 0 {
+            errors.push("Cage sums cannot be taken modulo 0".to_string());
+        }
+        for (i, cage) in sudoku.killer_cages.iter().enumerate() {
+            if matches!(cage.sum, Some(Clue::Known(sum)) if sum >= modulo) {
+                errors.push(format!("Killer cage {i} has a clue of {modulo} or more, which no sum modulo {modulo} gives"));
+            }
+        }
+    }
+    for (i, count) in sudoku.digit_counts.iter().enumerate() {
+        validate_lines("Digit count", std::slice::from_ref(&count.cells), size, 1, cells, &mut errors);
+        if !sudoku.digits.contains(&count.digit) {
+            errors.push(format!("Digit count {i} counts {}, which is not a digit", count.digit));
+        }
+    }
+    for (i, mask) in sudoku.masks.iter().enumerate() {
+        validate_lines(&format!("Mask {}", mask.rule.name()), std::slice::from_ref(&mask.cells), size, 1, cells, &mut errors);
+        if sudoku.digits.iter().all(|&digit| !mask.rule.allows(digit)) {
+            errors.push(format!("Mask {i} allows no digit"));
+        }
+    }
+    for (i, sandwich) in sudoku.sandwich.iter().enumerate() {
+        if sandwich.index >= size {
+            errors.push(format!("Sandwich {i} is outside the grid"));
+        }
+        if sandwich.crusts[0] == sandwich.crusts[1] || sandwich.crusts.iter().any(|crust| !sudoku.digits.contains(crust)) {
+            errors.push(format!("Sandwich {i} has invalid crusts {:?}", sandwich.crusts));
+        }
+    }
+    for (i, order) in sudoku.line_order.iter().enumerate() {
+        if order.lesser.index >= size || order.greater.index >= size {
+            errors.push(format!("Line order {i} is outside the grid"));
+        }
+    }
+    for (i, frame) in sudoku.frame.iter().enumerate() {
+        if frame.index >= size {
+            errors.push(format!("Frame {i} is outside the grid"));
+        }
+    }
+    for (i, skyscraper) in sudoku.skyscraper.iter().enumerate() {
+        if skyscraper.index >= size {
+            errors.push(format!("Skyscraper {i} is outside the grid"));
+        }
+        if let Clue::Known(count) = skyscraper.count {
+            if count == 0 || count > size as u64 {
+                errors.push(format!("Skyscraper {i} sees {count} squares, not between 1 and {size}"));
+            }
+        }
+    }
+    for (i, x_sum) in sudoku.x_sum.iter().enumerate() {
+        if x_sum.index >= size {
+            errors.push(format!("X-sum {i} is outside the grid"));
+        }
+    }
+    for unknown in &sudoku.unknowns {
+        if parse_cell(&unknown.name, usize::MAX).is_some() {
+            errors.push(format!("Unknown {} has the name of a square", unknown.name));
+        }
+        if unknown.min > unknown.max {
+            errors.push(format!("Unknown {} has no possible values", unknown.name));
+        }
+    }
+    let clues = sudoku.sandwich.iter().map(|sandwich| &sandwich.sum)
+        .chain(sudoku.frame.iter().map(|frame| &frame.sum))
+        .chain(sudoku.skyscraper.iter().map(|skyscraper| &skyscraper.count))
+        .chain(sudoku.x_sum.iter().map(|x_sum| &x_sum.sum))
+        .chain([&sudoku.whisper_difference])
+        .chain(sudoku.killer_cages.iter().filter_map(|cage| cage.sum.as_ref()))
+        .chain(sudoku.cage_sum_offset.as_ref())
+        .chain(sudoku.masks.iter().filter_map(|mask| match &mask.rule {
+            MaskRule::Sum(sum) => Some(sum),
+            _ => None,
+        }))
+        .chain(sudoku.digit_counts.iter().map(|count| &count.count));
+    for clue in clues {
+        if let Clue::Unknown(name) = clue {
+            if !sudoku.unknowns.iter().any(|unknown| &unknown.name == name) {
+                errors.push(format!("Unknown {name} is not declared"));
+            }
+        }
+    }
+    errors
+}
+
+fn undirected(line: &mut [Vec<usize>]) {
+    if line.iter().rev().lt(line.iter()) {
+        line.reverse();
+    }
+}
+
+pub fn normalize(sudoku: &Sudoku) -> Sudoku {
+    let mut normalized = sudoku.clone();
+    normalized.unknown_givens.sort();
+    normalized.fog.sort();
+    normalized.fog.dedup();
+    normalized.odd_shading.sort();
+    normalized.odd_shading.dedup();
+    normalized.odd_cells.sort();
+    normalized.odd_cells.dedup();
+    normalized.even_cells.sort();
+    normalized.even_cells.dedup();
+    for quadruple in &mut normalized.quadruples {
+        quadruple.digits.sort();
+    }
+    normalized.quadruples.sort_by(|a, b| (&a.cell, &a.digits).cmp(&(&b.cell, &b.digits)));
+    normalized.offset.sort();
+    normalized.offset.dedup();
+    for group in &mut normalized.offset_groups {
+        group.offsets.sort();
+        group.offsets.dedup();
+    }
+    normalized.offset_groups.sort_by(|a, b| a.name.cmp(&b.name));
+    for kropki in normalized.kropki_adjacent.iter_mut().chain(normalized.kropki_double.iter_mut()).chain(normalized.kropki_ambiguous.iter_mut()) {
+        kropki.sort();
+    }
+    for bar in normalized.consecutive_bars.iter_mut().flatten() {
+        bar.sort();
+    }
+    if let Some(bars) = &mut normalized.consecutive_bars {
+        bars.sort();
+    }
+    for whisper in &mut normalized.german_whispers {
+        undirected(whisper);
+    }
+    for renban in &mut normalized.renban {
+        undirected(renban);
+    }
+    for lockout in &mut normalized.lockout_lines {
+        undirected(lockout);
+    }
+    for entropic in &mut normalized.entropic_lines {
+        undirected(entropic);
+    }
+    for modular in &mut normalized.modular_lines {
+        undirected(modular);
+    }
+    for line in &mut normalized.region_sum_lines {
+        undirected(line);
+    }
+    normalized.thermo.sort();
+    normalized.arrow.sort();
+    normalized.kropki_adjacent.sort();
+    normalized.kropki_double.sort();
+    normalized.kropki_ambiguous.sort();
+    normalized.german_whispers.sort();
+    normalized.renban.sort();
+    normalized.lockout_lines.sort();
+    normalized.entropic_lines.sort();
+    normalized.modular_lines.sort();
+    normalized.region_sum_lines.sort();
+    normalized.row_indexing.sort();
+    normalized.row_indexing.dedup();
+    normalized.column_indexing.sort();
+    normalized.column_indexing.dedup();
+    for sandwich in &mut normalized.sandwich {
+        sandwich.crusts.sort();
+    }
+    for region in &mut normalized.digit_regions {
+        region.cells.sort();
+        region.digits.sort();
+        region.digits.dedup();
+    }
+    for cage in &mut normalized.killer_cages {
+        cage.cells.sort();
+        cage.cells.dedup();
+    }
+    normalized.killer_cages.sort_by(|a, b| a.cells.cmp(&b.cells));
+    for count in &mut normalized.digit_counts {
+        count.cells.sort();
+        count.cells.dedup();
+    }
+    for mask in &mut normalized.masks {
+        mask.cells.sort();
+        mask.cells.dedup();
+    }
+    normalized.digit_regions.sort_by(|a, b| (&a.cells, &a.digits).cmp(&(&b.cells, &b.digits)));
+    for mark in &mut normalized.state.pencilmarks {
+        mark.digits.sort();
+        mark.digits.dedup();
+    }
+    normalized.state.pencilmarks.sort_by(|a, b| a.cell.cmp(&b.cell));
+    normalized.sandwich.sort_by_key(|sandwich| (sandwich.side, sandwich.index));
+    normalized.frame.sort_by_key(|frame| (frame.side, frame.index));
+    normalized.skyscraper.sort_by_key(|skyscraper| (skyscraper.side, skyscraper.index));
+    normalized.x_sum.sort_by_key(|x_sum| (x_sum.side, x_sum.index));
+    normalized.line_order.sort_by_key(|order| (order.lesser, order.greater));
+    normalized.unknowns.sort_by(|a, b| a.name.cmp(&b.name));
+    normalized
+}
+
+pub fn sudoku_to_json(sudoku: &Sudoku) -> Value {
+    let offset_groups = sudoku.offset_groups.iter().map(|group| {
+        let mut value = json!({
+            "name": group.name,
+            "offsets": group.offsets,
+        });
+        match group.relation {
+            OffsetRelation::NotEqual => value["relation"] = json!("notEqual"),
+            OffsetRelation::NotConsecutive => value["relation"] = json!("notConsecutive"),
+            OffsetRelation::AtLeast(difference) => {
+                value["relation"] = json!("atLeast");
+                value["difference"] = json!(difference);
+            }
+        }
+        value
+    }).collect::<Vec<_>>();
+    let sandwich = sudoku.sandwich.iter().map(|sandwich| json!({
+        "side": sandwich.side.name(),
+        "index": sandwich.index,
+        "sum": sandwich.sum.to_json(),
+        "crusts": sandwich.crusts,
+    })).collect::<Vec<_>>();
+    let reading = |reading: &Reading| json!({"side": reading.side.name(), "index": reading.index});
+    let line_order = sudoku.line_order.iter().map(|order| json!({
+        "lesser": reading(&order.lesser),
+        "greater": reading(&order.greater),
+    })).collect::<Vec<_>>();
+    let frame = sudoku.frame.iter().map(|frame| json!({
+        "side": frame.side.name(),
+        "index": frame.index,
+        "sum": frame.sum.to_json(),
+    })).collect::<Vec<_>>();
+    let skyscraper = sudoku.skyscraper.iter().map(|skyscraper| json!({
+        "side": skyscraper.side.name(),
+        "index": skyscraper.index,
+        "count": skyscraper.count.to_json(),
+    })).collect::<Vec<_>>();
+    let x_sum = sudoku.x_sum.iter().map(|x_sum| json!({
+        "side": x_sum.side.name(),
+        "index": x_sum.index,
+        "sum": x_sum.sum.to_json(),
+    })).collect::<Vec<_>>();
+    let soft = sudoku.soft.iter().map(|soft| {
+        let mut value = json!({
+            "rule": soft.rule.name(),
+            "cells": soft.cells,
+            "weight": soft.weight,
+        });
+        if let SoftRule::Given(given) = soft.rule {
+            value["value"] = json!(given);
+        }
+        value
+    }).collect::<Vec<_>>();
+    // Blank squares stay 0 unless 0 is a digit of the puzzle
+    let blank = if sudoku.digits.contains(&0) { Value::Null } else { json!(0) };
+    let given = sudoku.given.iter().map(
+        |row| row.iter().map(|given| given.map_or(blank.clone(), |given| json!(given))).collect::<Vec<_>>()
+    ).collect::<Vec<_>>();
+    let arrow = sudoku.arrow.iter().map(|arrow| {
+        if arrow.pill == 1 && !arrow.distinct {
+            return json!(arrow.cells);
+        }
+        let mut value = json!({"cells": arrow.cells});
+        if arrow.pill != 1 {
+            value["pill"] = json!(arrow.pill);
+        }
+        if arrow.distinct {
+            value["distinct"] = json!(true);
+        }
+        value
+    }).collect::<Vec<_>>();
+    let unknowns = sudoku.unknowns.iter().map(|unknown| json!({
+        "name": unknown.name,
+        "min": unknown.min,
+        "max": unknown.max,
+    })).collect::<Vec<_>>();
+    let killer_cages = sudoku.killer_cages.iter().map(|cage| {
+        let mut value = json!({"cells": cage.cells});
+        if let Some(sum) = &cage.sum {
+            value["sum"] = sum.to_json();
+        }
+        if cage.relation != CageRelation::Equal {
+            value["relation"] = json!(cage.relation.name());
+        }
+        value
+    }).collect::<Vec<_>>();
+    let digit_counts = sudoku.digit_counts.iter().map(|count| json!({
+        "cells": count.cells,
+        "digit": count.digit,
+        "relation": count.relation.name(),
+        "count": count.count.to_json(),
+    })).collect::<Vec<_>>();
+    let masks = sudoku.masks.iter().map(|mask| {
+        let mut value = json!({
+            "cells": mask.cells,
+            "rule": mask.rule.name(),
+        });
+        match &mask.rule {
+            MaskRule::AtLeast(bound) | MaskRule::AtMost(bound) => value["value"] = json!(bound),
+            MaskRule::Sum(sum) => value["value"] = sum.to_json(),
+            MaskRule::Odd | MaskRule::Even | MaskRule::Distinct => {}
+        }
+        value
+    }).collect::<Vec<_>>();
+    let quadruples = sudoku.quadruples.iter().map(|quadruple| json!({
+        "cell": quadruple.cell,
+        "digits": quadruple.digits,
+    })).collect::<Vec<_>>();
+    let digit_regions = sudoku.digit_regions.iter().map(|region| json!({
+        "cells": region.cells,
+        "digits": region.digits,
+    })).collect::<Vec<_>>();
+    let mut value = json!({
+        "given": given,
+        "unknownGivens": sudoku.unknown_givens,
+        "fog": sudoku.fog,
+        "1-9horiz": sudoku.horizontal_rule,
+        "1-9vert": sudoku.vertical_rule,
+        "1-9nonet": sudoku.nonet_rule,
+        "windoku": sudoku.windoku,
+        "disjointGroups": sudoku.disjoint_groups,
+        "diagPos": sudoku.positive_diagonal,
+        "diagNeg": sudoku.negative_diagonal,
+        "offsets": sudoku.offset,
+        "offsetGroups": offset_groups,
+        "taxicab": sudoku.taxicab,
+        "nonConsecutive": sudoku.non_consecutive,
+        "oddShading": sudoku.odd_shading,
+        "oddCells": sudoku.odd_cells,
+        "evenCells": sudoku.even_cells,
+        "quadruples": quadruples,
+        "thermo": sudoku.thermo,
+        "arrow": arrow,
+        "kropkiAdjacent": sudoku.kropki_adjacent,
+        "kropkiDouble": sudoku.kropki_double,
+        "kropkiAmbiguous": sudoku.kropki_ambiguous,
+        "germanWhispers": sudoku.german_whispers,
+        "renban": sudoku.renban,
+        "lockoutLines": sudoku.lockout_lines,
+        "entropicLines": sudoku.entropic_lines,
+        "modularLines": sudoku.modular_lines,
+        "regionSumLines": sudoku.region_sum_lines,
+        "whisperDifference": sudoku.whisper_difference.to_json(),
+        "rowIndexing": sudoku.row_indexing,
+        "columnIndexing": sudoku.column_indexing,
+        "sandwich": sandwich,
+        "frame": frame,
+        "skyscraper": skyscraper,
+        "xSum": x_sum,
+        "lineOrder": line_order,
+        "soft": soft,
+        "digitRegions": digit_regions,
+        "masks": masks,
+        "digitCounts": digit_counts,
+        "killerCages": killer_cages,
+        "distinctCageSums": sudoku.distinct_cage_sums,
+        "cageAntiAdjacency": sudoku.cage_anti_adjacency,
+        "unknowns": unknowns,
+    });
+    if let Some(bars) = &sudoku.consecutive_bars {
+        value["consecutiveBars"] = json!(bars);
+    }
+    if let Some(modulo) = sudoku.cage_sum_modulo {
+        value["cageSumModulo"] = json!(modulo);
+    }
+    if let Some(offset) = &sudoku.cage_sum_offset {
+        value["cageSumOffset"] = offset.to_json();
+    }
+    if sudoku.state.filled.iter().flatten().any(|filled| filled.is_some()) || !sudoku.state.pencilmarks.is_empty() {
+        let filled = sudoku.state.filled.iter().map(
+            |row| row.iter().map(|filled| filled.map_or(blank.clone(), |filled| json!(filled))).collect::<Vec<_>>()
+        ).collect::<Vec<_>>();
+        let pencilmarks = sudoku.state.pencilmarks.iter().map(|mark| json!({
+            "cell": mark.cell,
+            "digits": mark.digits,
+        })).collect::<Vec<_>>();
+        value["state"] = json!({"filled": filled, "pencilmarks": pencilmarks});
+    }
+    if sudoku.contiguous() {
+        value["digits"] = json!(sudoku.crusts());
+    } else {
+        value["digitSet"] = json!(sudoku.digits);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic_sudoku;
+
+    const EMPTY: [&str; 9] = ["........."; 9];
+
+    #[test]
+    fn validate_accepts_classic() {
+        assert!(validate(&classic_sudoku(&EMPTY), Adjacency::Diagonal).is_empty());
+    }
+
+    #[test]
+    fn validate_reports_bad_lines() {
+        let mut sudoku = classic_sudoku(&EMPTY);
+        sudoku.thermo = vec![vec![vec![0, 0], vec![0, 2]], vec![vec![8, 8], vec![9, 8]]];
+        sudoku.renban = vec![(0..10).map(|j| vec![1, j.min(8)]).collect()];
+        let errors = validate(&sudoku, Adjacency::Orthogonal);
+        assert!(errors.contains(&"Thermo 0 jumps from [0, 0] to [0, 2]".to_string()), "{errors:?}");
+        assert!(errors.contains(&"Thermo 1 contains invalid square [9, 8]".to_string()), "{errors:?}");
+        assert!(errors.contains(&"Renban 0 has 10 squares".to_string()), "{errors:?}");
+    }
+
+    #[test]
+    fn normalize_orders_and_dedups() {
+        let mut sudoku = classic_sudoku(&EMPTY);
+        sudoku.fog = vec![vec![4, 4], vec![0, 1], vec![4, 4]];
+        sudoku.german_whispers = vec![vec![vec![2, 2], vec![2, 1], vec![2, 0]]];
+        sudoku.kropki_double = vec![vec![vec![5, 1], vec![5, 0]]];
+        sudoku.thermo = vec![vec![vec![3, 0], vec![3, 1]], vec![vec![1, 0], vec![1, 1]]];
+        let normalized = normalize(&sudoku);
+        assert_eq!(normalized.fog, [[0, 1], [4, 4]]);
+        assert_eq!(normalized.german_whispers, [[[2, 0], [2, 1], [2, 2]]]);
+        assert_eq!(normalized.kropki_double, [[[5, 0], [5, 1]]]);
+        assert_eq!(normalized.thermo, [[[1, 0], [1, 1]], [[3, 0], [3, 1]]]);
+        assert_eq!(sudoku_to_json(&normalize(&normalized)), sudoku_to_json(&normalized));
+    }
+}
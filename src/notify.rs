@@ -0,0 +1,31 @@
+use std::process::Command;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+/// Posts a summary of a finished run to a webhook. The message is in `content` so Discord shows it, and the
+/// run, result and timing are also given as fields for other services. curl does the posting so that https
+/// works without a TLS library.
+pub fn notify(url: &str, metadata: &Value, result: Option<&str>, elapsed: Duration) {
+    let file = metadata["file"].as_str().unwrap_or("puzzle");
+    let mode = metadata["mode"].as_str().unwrap_or("run");
+    let message = match result {
+        Some(result) => format!("sudoku-z3 {mode} of {file} finished in {:.1}s: {result}", elapsed.as_secs_f64()),
+        None => format!("sudoku-z3 {mode} of {file} finished in {:.1}s", elapsed.as_secs_f64()),
+    };
+    let summary = json!({
+        "content": message,
+        "run": metadata,
+        "result": result,
+        "elapsedMs": elapsed.as_millis() as u64,
+    });
+    let posted = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "10", "--header", "Content-Type: application/json"])
+        .args(["--data", &summary.to_string(), url])
+        .status();
+    match posted {
+        Ok(status) if status.success() => {}
+        Ok(_) => eprintln!("Could not notify {url}."),
+        Err(error) => eprintln!("Could not notify {url}, curl is needed to post: {error}."),
+    }
+}
@@ -0,0 +1,37 @@
+use z3::Context;
+use z3::ast::Int;
+
+use crate::parse_cell;
+
+fn parse_term<'ctx>(term: &str, grid: &[Vec<Int<'ctx>>], ctx: &'ctx Context) -> Result<Int<'ctx>, String> {
+    let (coefficient, cell) = match term.split_once('*') {
+        Some((a, b)) if parse_cell(b, grid.len()).is_some() => (a, Some(b)),
+        Some((a, b)) => (b, Some(a)),
+        None if parse_cell(term, grid.len()).is_some() => ("1", Some(term)),
+        None => (term, None),
+    };
+    let coefficient = coefficient.parse::<i64>().map_err(|_| format!("Invalid term {term}"))?;
+    match cell {
+        Some(cell) => {
+            let (i, j) = parse_cell(cell, grid.len()).ok_or_else(|| format!("Invalid square {cell}"))?;
+            Ok(Int::mul(ctx, &[&Int::from_i64(ctx, coefficient), &grid[i][j]]))
+        }
+        None => Ok(Int::from_i64(ctx, coefficient)),
+    }
+}
+
+/// Parses a linear expression over squares such as `r0c0 + 2*r1c1 - 3`
+pub fn parse_linear<'ctx>(expression: &str, grid: &[Vec<Int<'ctx>>], ctx: &'ctx Context) -> Result<Int<'ctx>, String> {
+    let expression = expression.replace(' ', "").replace('-', "+-");
+    let mut terms = Vec::new();
+    for term in expression.split('+').filter(|term| !term.is_empty()) {
+        match term.strip_prefix('-') {
+            Some(term) => terms.push(Int::unary_minus(&parse_term(term, grid, ctx)?)),
+            None => terms.push(parse_term(term, grid, ctx)?),
+        }
+    }
+    if terms.is_empty() {
+        return Err("Empty objective".to_string());
+    }
+    Ok(Int::add(ctx, &terms.iter().collect::<Vec<_>>()))
+}
@@ -1,14 +1,27 @@
 use z3::{Context, Optimize};
 use z3::ast::{Ast, Int, Bool};
 
-use crate::Sudoku;
+use crate::{box_segments, cage_clue_ast, cage_sum, disjoint_groups, nonets, windows, ORTHOGONAL, Sudoku, Cage, CageRelation, CountRelation, DigitCount, DigitRegion, Frame, LineOrder, Mask, MaskRule, OffsetRelation, Quadruple, Reading, Sandwich, Skyscraper, XSum, SoftConstraint, SoftRule};
 
-fn add_number_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
+fn add_unknown_constraints(sudoku: &Sudoku, optimizer: &Optimize, ctx: &Context) {
+  for unknown in &sudoku.unknowns {
+      let ast = Int::new_const(ctx, unknown.name.as_str());
+      optimizer.assert(&ast.ge(&Int::from_i64(ctx, unknown.min)));
+      optimizer.assert(&ast.le(&Int::from_i64(ctx, unknown.max)));
+  }
+}
+
+fn add_number_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
   let mut number_constraints = Vec::new();
-  for i in 0..9 {
-      for j in 0..9 {
-          number_constraints.push(grid[i][j].ge(&Int::from_u64(ctx, 1)));
-          number_constraints.push(grid[i][j].le(&Int::from_u64(ctx, 9)));
+  for row in grid {
+      for square in row {
+          if sudoku.contiguous() {
+              let [low, high] = sudoku.crusts();
+              number_constraints.push(square.ge(&Int::from_u64(ctx, low)));
+              number_constraints.push(square.le(&Int::from_u64(ctx, high)));
+          } else {
+              number_constraints.push(digit_set_ast(square, &sudoku.digits, ctx));
+          }
       }
   }
   for number_constraint in number_constraints {
@@ -16,14 +29,24 @@ fn add_number_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &
   }
 }
 
-fn add_given_constraints(sudoku: &Sudoku, grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
+fn digit_set_ast<'ctx>(square: &Int<'ctx>, digits: &[u64], ctx: &'ctx Context) -> Bool<'ctx> {
+  let options = digits.iter().map(|&digit| square._eq(&Int::from_u64(ctx, digit))).collect::<Vec<_>>();
+  Bool::or(ctx, &options.iter().collect::<Vec<_>>())
+}
+
+fn add_digit_region_constraint(grid: &[Vec<Int<'_>>], region: &DigitRegion, optimizer: &Optimize, ctx: &Context) {
+  for cell in &region.cells {
+      optimizer.assert(&digit_set_ast(&grid[cell[0]][cell[1]], &region.digits, ctx));
+  }
+}
+
+fn add_given_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
   let mut given_constraints = Vec::new();
-  for i in 0..9 {
-      for j in 0..9 {
-          if sudoku.given[i][j] < 1 || sudoku.given[i][j] > 9 {
-              continue;
+  for (row, givens) in grid.iter().zip(&sudoku.given) {
+      for (square, given) in row.iter().zip(givens) {
+          if let Some(given) = given {
+              given_constraints.push(square._eq(&Int::from_u64(ctx, *given)));
           }
-          given_constraints.push(grid[i][j]._eq(&Int::from_u64(ctx, sudoku.given[i][j])));
       }
   }
   for given_constraint in given_constraints {
@@ -31,27 +54,33 @@ fn add_given_constraints(sudoku: &Sudoku, grid: &Vec<Vec<Int<'_>>>, optimizer: &
   }
 }
 
-fn add_horizontal_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
-  let mut horizontal_constraints = Vec::new();
-  for i in 0..9 {
-      let mut row = Vec::new();
-      for j in 0..9 {
-          row.push(&grid[i][j]);
+fn add_state_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  for (i, row) in sudoku.state.filled.iter().enumerate() {
+      for (j, filled) in row.iter().enumerate() {
+          if let Some(digit) = filled {
+              optimizer.assert(&grid[i][j]._eq(&Int::from_u64(ctx, *digit)));
+          }
       }
-      horizontal_constraints.push(Int::distinct(ctx, &row));
+  }
+  for mark in &sudoku.state.pencilmarks {
+      optimizer.assert(&digit_set_ast(&grid[mark.cell[0]][mark.cell[1]], &mark.digits, ctx));
+  }
+}
+
+fn add_horizontal_constraints(grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  let mut horizontal_constraints = Vec::new();
+  for row in grid {
+      horizontal_constraints.push(Int::distinct(ctx, &row.iter().collect::<Vec<_>>()));
   }
   for horizontal_constraint in horizontal_constraints {
       optimizer.assert(&horizontal_constraint);
   }
 }
 
-fn add_vertical_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
+fn add_vertical_constraints(grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
   let mut vertical_constraints = Vec::new();
-  for i in 0..9 {
-      let mut col = Vec::new();
-      for j in 0..9 {
-          col.push(&grid[j][i]);
-      }
+  for i in 0..grid.len() {
+      let col = grid.iter().map(|row| &row[i]).collect::<Vec<_>>();
       vertical_constraints.push(Int::distinct(ctx, &col));
   }
   for vertical_constraint in vertical_constraints {
@@ -59,27 +88,32 @@ fn add_vertical_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx:
   }
 }
 
-fn add_nonet_constraints(grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
-  let mut nonet_constraints = Vec::new();
-  for i in 0..9 {
-      let mut nonet = Vec::new();
-      for j in 0..9 {
-          nonet.push(&grid[((i / 3) * 3) + (j / 3)][((i % 3) * 3) + (j % 3)]);
-      }
-      nonet_constraints.push(Int::distinct(ctx, &nonet));
-  }
-  for nonet_constraint in nonet_constraints {
-      optimizer.assert(&nonet_constraint);
+fn add_region_constraints(grid: &[Vec<Int<'_>>], regions: &[Vec<(usize, usize)>], optimizer: &Optimize, ctx: &Context) {
+  for region in regions {
+      let squares = region.iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+      optimizer.assert(&Int::distinct(ctx, &squares));
   }
 }
 
-fn add_offset_constraint(grid: &Vec<Vec<Int<'_>>>, offsets: &Vec<Vec<i32>>, optimizer: &Optimize) {
+fn add_offset_constraint(grid: &[Vec<Int<'_>>], offsets: &[Vec<i32>], relation: &OffsetRelation, optimizer: &Optimize, ctx: &Context) {
+  let size = grid.len() as i32;
   let mut offset_constraints = Vec::new();
-  for i in 0..9 {
-      for j in 0..9 {
-          let squares = offsets.iter().map(|x| ((i as i32) + x[0], (j as i32) + x[1])).filter(|(a, b)| 0 <= *a && *a < 9 && 0 <= *b && *b < 9);
+  for i in 0..grid.len() {
+      for j in 0..grid.len() {
+          let squares = offsets.iter().map(|x| ((i as i32) + x[0], (j as i32) + x[1])).filter(|(a, b)| 0 <= *a && *a < size && 0 <= *b && *b < size);
           for (row, col) in squares {
-              offset_constraints.push(Bool::not(&grid[i][j]._eq(&grid[row as usize][col as usize])));
+              let (a, b) = (&grid[i][j], &grid[row as usize][col as usize]);
+              offset_constraints.push(match relation {
+                  OffsetRelation::NotEqual => Bool::not(&a._eq(b)),
+                  OffsetRelation::NotConsecutive => Bool::and(ctx, &[
+                      &Bool::not(&Int::sub(ctx, &[a, b])._eq(&Int::from_u64(ctx, 1))),
+                      &Bool::not(&Int::sub(ctx, &[b, a])._eq(&Int::from_u64(ctx, 1))),
+                  ]),
+                  OffsetRelation::AtLeast(diff) => Bool::or(ctx, &[
+                      &Int::sub(ctx, &[a, b]).ge(&Int::from_u64(ctx, *diff)),
+                      &Int::sub(ctx, &[b, a]).ge(&Int::from_u64(ctx, *diff)),
+                  ]),
+              });
           }
       }
   }
@@ -88,57 +122,356 @@ fn add_offset_constraint(grid: &Vec<Vec<Int<'_>>>, offsets: &Vec<Vec<i32>>, opti
   }
 }
 
-fn add_increasing_constraint(grid: &Vec<Vec<Int<'_>>>, squares: &Vec<Vec<usize>>, optimizer: &Optimize) {
+fn add_odd_shading_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  let (odd, even): (Vec<u64>, Vec<u64>) = sudoku.digits.iter().partition(|&&digit| digit % 2 == 1);
+  for (i, row) in grid.iter().enumerate() {
+      for (j, square) in row.iter().enumerate() {
+          let shaded = sudoku.odd_shading.iter().any(|cell| cell[0] == i && cell[1] == j);
+          optimizer.assert(&digit_set_ast(square, if shaded { &odd } else { &even }, ctx));
+      }
+  }
+}
+
+fn add_parity_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  let (odd, even): (Vec<u64>, Vec<u64>) = sudoku.digits.iter().partition(|&&digit| digit % 2 == 1);
+  for (cells, digits) in [(&sudoku.odd_cells, &odd), (&sudoku.even_cells, &even)] {
+      for cell in cells {
+          optimizer.assert(&digit_set_ast(&grid[cell[0]][cell[1]], digits, ctx));
+      }
+  }
+}
+
+/// Every listed digit appears among the four squares at least as often as it is listed
+fn add_quadruple_constraint(grid: &[Vec<Int<'_>>], quadruple: &Quadruple, optimizer: &Optimize, ctx: &Context) {
+  let (one, zero) = (Int::from_u64(ctx, 1), Int::from_u64(ctx, 0));
+  let mut digits = quadruple.digits.clone();
+  digits.sort();
+  digits.dedup();
+  for digit in digits {
+      let times = quadruple.digits.iter().filter(|&&listed| listed == digit).count() as u64;
+      let hits = quadruple.cells().iter().map(|cell| grid[cell[0]][cell[1]]._eq(&Int::from_u64(ctx, digit)).ite(&one, &zero)).collect::<Vec<_>>();
+      optimizer.assert(&Int::add(ctx, &hits.iter().collect::<Vec<_>>()).ge(&Int::from_u64(ctx, times)));
+  }
+}
+
+fn add_mask_constraint(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], mask: &Mask, optimizer: &Optimize, ctx: &Context) {
+  let squares = mask.cells.iter().map(|cell| &grid[cell[0]][cell[1]]).collect::<Vec<_>>();
+  match &mask.rule {
+      MaskRule::Distinct => optimizer.assert(&Int::distinct(ctx, &squares)),
+      MaskRule::Sum(sum) => optimizer.assert(&Int::add(ctx, &squares)._eq(&sum.ast(ctx))),
+      rule => {
+          let allowed = sudoku.digits.iter().copied().filter(|&digit| rule.allows(digit)).collect::<Vec<_>>();
+          for square in squares {
+              optimizer.assert(&digit_set_ast(square, &allowed, ctx));
+          }
+      }
+  }
+}
+
+fn add_cage_anti_adjacency_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize) {
+  let size = grid.len();
+  let mut cage_of = vec![vec![None; size]; size];
+  for (i, cage) in sudoku.killer_cages.iter().enumerate() {
+      for cell in &cage.cells {
+          cage_of[cell[0]][cell[1]] = Some(i);
+      }
+  }
+  for row in 0..size {
+      for column in 0..size {
+          // Only look down and right so each adjacent pair is constrained once
+          for (r, c) in [(row + 1, column), (row, column + 1)] {
+              if r < size && c < size && cage_of[row][column] != cage_of[r][c] {
+                  optimizer.assert(&grid[row][column]._eq(&grid[r][c]).not());
+              }
+          }
+      }
+  }
+}
+
+fn add_cage_constraint(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], index: usize, cage: &Cage, optimizer: &Optimize, ctx: &Context) {
+  let squares = cage.cells.iter().map(|cell| &grid[cell[0]][cell[1]]).collect::<Vec<_>>();
+  optimizer.assert(&Int::distinct(ctx, &squares));
+  optimizer.assert(&Int::add(ctx, &squares)._eq(&cage_sum(sudoku, index, ctx)));
+  if let Some(sum) = &cage.sum {
+      let total = cage_clue_ast(sudoku, index, ctx);
+      optimizer.assert(&match cage.relation {
+          CageRelation::Equal => total._eq(&sum.ast(ctx)),
+          CageRelation::LessThan => total.lt(&sum.ast(ctx)),
+          CageRelation::GreaterThan => total.gt(&sum.ast(ctx)),
+      });
+  }
+}
+
+/// The digits of a line as the positional number they spell, in a base larger than every digit
+fn reading_ast<'ctx>(sudoku: &Sudoku, grid: &[Vec<Int<'ctx>>], reading: &Reading, ctx: &'ctx Context) -> Int<'ctx> {
+  let base = Int::from_u64(ctx, sudoku.digits.last().map_or(10, |&digit| (digit + 1).max(10)));
+  reading.side.line(reading.index, grid.len()).into_iter().fold(
+      Int::from_u64(ctx, 0),
+      |number, (i, j)| Int::add(ctx, &[&Int::mul(ctx, &[&number, &base]), &grid[i][j]]),
+  )
+}
+
+fn add_line_order_constraint(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], order: &LineOrder, optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&reading_ast(sudoku, grid, &order.lesser, ctx).lt(&reading_ast(sudoku, grid, &order.greater, ctx)));
+}
+
+fn add_digit_count_constraint(grid: &[Vec<Int<'_>>], count: &DigitCount, optimizer: &Optimize, ctx: &Context) {
+  let digit = Int::from_u64(ctx, count.digit);
+  let (one, zero) = (Int::from_u64(ctx, 1), Int::from_u64(ctx, 0));
+  let hits = count.cells.iter().map(|cell| grid[cell[0]][cell[1]]._eq(&digit).ite(&one, &zero)).collect::<Vec<_>>();
+  let total = Int::add(ctx, &hits.iter().collect::<Vec<_>>());
+  let target = count.count.ast(ctx);
+  optimizer.assert(&match count.relation {
+      CountRelation::Exactly => total._eq(&target),
+      CountRelation::AtLeast => total.ge(&target),
+      CountRelation::AtMost => total.le(&target),
+  });
+}
+
+/// Distinct digits whose largest and smallest differ by less than the length of the line, so they are consecutive
+fn add_renban_constraint(grid: &[Vec<Int<'_>>], renban: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  let squares = renban.iter().map(|cell| &grid[cell[0]][cell[1]]).collect::<Vec<_>>();
+  optimizer.assert(&Int::distinct(ctx, &squares));
+  let spread = Int::from_u64(ctx, renban.len() as u64 - 1);
+  for (k, a) in squares.iter().enumerate() {
+      for b in &squares[k + 1..] {
+          optimizer.assert(&Int::sub(ctx, &[*a, *b]).le(&spread));
+          optimizer.assert(&Int::sub(ctx, &[*b, *a]).le(&spread));
+      }
+  }
+}
+
+fn add_taxicab_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  let size = grid.len();
+  let mut taxicab_constraints = Vec::new();
+  for i in 0..size * size {
+      for j in (i + 1)..size * size {
+          let (a, b) = ((i / size, i % size), (j / size, j % size));
+          let distance = (a.0.abs_diff(b.0) + a.1.abs_diff(b.1)) as u64;
+          if !sudoku.digits.contains(&distance) {
+              continue;
+          }
+          let digit = Int::from_u64(ctx, distance);
+          taxicab_constraints.push(Bool::not(&Bool::and(ctx, &[&grid[a.0][a.1]._eq(&digit), &grid[b.0][b.1]._eq(&digit)])));
+      }
+  }
+  for taxicab_constraint in taxicab_constraints {
+      optimizer.assert(&taxicab_constraint);
+  }
+}
+
+fn increasing_ast<'ctx>(grid: &[Vec<Int<'ctx>>], squares: &[Vec<usize>], ctx: &'ctx Context) -> Bool<'ctx> {
   let mut increasing_constraints = Vec::new();
   for i in 0..squares.len() - 1 {
       increasing_constraints.push(grid[squares[i][0]][squares[i][1]].lt(&grid[squares[i+1][0]][squares[i+1][1]]));
   }
-  for increasing_constraint in increasing_constraints {
-      optimizer.assert(&increasing_constraint);
+  Bool::and(ctx, &increasing_constraints.iter().collect::<Vec<_>>())
+}
+
+fn add_increasing_constraint(grid: &[Vec<Int<'_>>], squares: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&increasing_ast(grid, squares, ctx));
+}
+
+/// Reads squares as the digits of a number, most significant first
+fn number_ast<'ctx>(grid: &[Vec<Int<'ctx>>], squares: &[Vec<usize>], ctx: &'ctx Context) -> Int<'ctx> {
+  let mut number = grid[squares[0][0]][squares[0][1]].clone();
+  for x in &squares[1..] {
+      number = Int::add(ctx, &[&Int::mul(ctx, &[&number, &Int::from_u64(ctx, 10)]), &grid[x[0]][x[1]]]);
   }
+  number
 }
 
-fn add_sum_constraint(grid: &Vec<Vec<Int<'_>>>, summands: &[Vec<usize>], sum: &Vec<usize>, optimizer: &Optimize, ctx: &Context) {
-  if summands.len() == 0 {
+fn sum_ast<'ctx>(grid: &[Vec<Int<'ctx>>], summands: &[Vec<usize>], sum: &[Vec<usize>], ctx: &'ctx Context) -> Bool<'ctx> {
+  if summands.is_empty() {
       panic!("No summands found");
   }
   let sum_ast = Int::add(ctx, &summands.iter().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>()[..]);
-  optimizer.assert(&grid[sum[0]][sum[1]]._eq(&sum_ast));
+  number_ast(grid, sum, ctx)._eq(&sum_ast)
 }
 
-fn add_exact_diff_constraint(grid: &Vec<Vec<Int<'_>>>, pair: &Vec<Vec<usize>>, diff: u64, optimizer: &Optimize, ctx: &Context) {
-  let fst_diff_ast = Int::sub(ctx, &pair.iter().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>());
-  let snd_diff_ast = Int::sub(ctx, &pair.iter().rev().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>());
-  optimizer.assert(&Bool::or(ctx, &[&fst_diff_ast._eq(&Int::from_u64(ctx, diff)), &snd_diff_ast._eq(&Int::from_u64(ctx, diff))]));
+fn add_sum_constraint(grid: &[Vec<Int<'_>>], summands: &[Vec<usize>], sum: &[Vec<usize>], distinct: bool, optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&sum_ast(grid, summands, sum, ctx));
+  if distinct {
+      optimizer.assert(&Int::distinct(ctx, &summands.iter().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>()));
+  }
 }
 
-fn add_at_least_diff_constraint(grid: &Vec<Vec<Int<'_>>>, pair: &[&Vec<usize>; 2], diff: u64, optimizer: &Optimize, ctx: &Context) {
+fn exact_diff_ast<'ctx>(grid: &[Vec<Int<'ctx>>], pair: &[Vec<usize>], diff: u64, ctx: &'ctx Context) -> Bool<'ctx> {
   let fst_diff_ast = Int::sub(ctx, &pair.iter().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>());
   let snd_diff_ast = Int::sub(ctx, &pair.iter().rev().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>());
-  optimizer.assert(&Bool::or(ctx, &[&fst_diff_ast.ge(&Int::from_u64(ctx, diff)), &snd_diff_ast.ge(&Int::from_u64(ctx, diff))]));
+  Bool::or(ctx, &[&fst_diff_ast._eq(&Int::from_u64(ctx, diff)), &snd_diff_ast._eq(&Int::from_u64(ctx, diff))])
+}
+
+fn add_exact_diff_constraint(grid: &[Vec<Int<'_>>], pair: &[Vec<usize>], diff: u64, optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&exact_diff_ast(grid, pair, diff, ctx));
+}
+
+fn at_least_diff_ast<'ctx>(grid: &[Vec<Int<'ctx>>], a: &[usize], b: &[usize], diff: &Int<'ctx>, ctx: &'ctx Context) -> Bool<'ctx> {
+  let fst_diff_ast = Int::sub(ctx, &[&grid[a[0]][a[1]], &grid[b[0]][b[1]]]);
+  let snd_diff_ast = Int::sub(ctx, &[&grid[b[0]][b[1]], &grid[a[0]][a[1]]]);
+  Bool::or(ctx, &[&fst_diff_ast.ge(diff), &snd_diff_ast.ge(diff)])
 }
 
-fn add_kropki_double_constraint(grid: &Vec<Vec<Int<'_>>>, pair: &Vec<Vec<usize>>, optimizer: &Optimize, ctx: &Context) {
+fn add_at_least_diff_constraint<'ctx>(grid: &[Vec<Int<'ctx>>], pair: &[&Vec<usize>; 2], diff: &Int<'ctx>, optimizer: &Optimize, ctx: &'ctx Context) {
+  optimizer.assert(&at_least_diff_ast(grid, pair[0], pair[1], diff, ctx));
+}
+
+/// The ends of the line differ by at least 4 and every square between them is below both ends or above both
+fn add_lockout_constraint(grid: &[Vec<Int<'_>>], lockout: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  let (first, last) = (&lockout[0], &lockout[lockout.len() - 1]);
+  add_at_least_diff_constraint(grid, &[first, last], &Int::from_u64(ctx, 4), optimizer, ctx);
+  let ends = [&grid[first[0]][first[1]], &grid[last[0]][last[1]]];
+  for cell in &lockout[1..lockout.len() - 1] {
+      let square = &grid[cell[0]][cell[1]];
+      let below = Bool::and(ctx, &[&square.lt(ends[0]), &square.lt(ends[1])]);
+      let above = Bool::and(ctx, &[&square.gt(ends[0]), &square.gt(ends[1])]);
+      optimizer.assert(&Bool::or(ctx, &[&below, &above]));
+  }
+}
+
+/// Every three squares in a row on the line hold one digit from each third of the digits, low, middle and high
+fn add_entropic_constraint(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], entropic: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  for window in entropic.windows(3) {
+      for class in sudoku.digits.chunks(sudoku.digits.len() / 3) {
+          let members = window.iter().map(|cell| digit_set_ast(&grid[cell[0]][cell[1]], class, ctx)).collect::<Vec<_>>();
+          optimizer.assert(&Bool::or(ctx, &members.iter().collect::<Vec<_>>()));
+      }
+  }
+}
+
+/// Every three squares in a row on the line hold digits with different remainders modulo 3
+fn add_modular_constraint(grid: &[Vec<Int<'_>>], modular: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  let three = Int::from_u64(ctx, 3);
+  for window in modular.windows(3) {
+      let remainders = window.iter().map(|cell| grid[cell[0]][cell[1]].modulo(&three)).collect::<Vec<_>>();
+      optimizer.assert(&Int::distinct(ctx, &remainders.iter().collect::<Vec<_>>()));
+  }
+}
+
+/// The segments of the line in every box it passes through add up to the same sum
+fn add_region_sum_constraint(grid: &[Vec<Int<'_>>], line: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  let sums = box_segments(line, grid.len()).iter().map(|segment| {
+      Int::add(ctx, &segment.iter().map(|cell| &grid[cell[0]][cell[1]]).collect::<Vec<_>>())
+  }).collect::<Vec<_>>();
+  if let Some((first, rest)) = sums.split_first() {
+      for sum in rest {
+          optimizer.assert(&sum._eq(first));
+      }
+  }
+}
+
+fn kropki_double_ast<'ctx>(grid: &[Vec<Int<'ctx>>], pair: &[Vec<usize>], ctx: &'ctx Context) -> Bool<'ctx> {
   let asts = &pair.iter().map(|x| &grid[x[0]][x[1]]).collect::<Vec<_>>()[..];
-  optimizer.assert(
-      &Bool::or(ctx,
-          &[
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 1)), &asts[1]._eq(&Int::from_u64(ctx, 2))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 2)), &asts[1]._eq(&Int::from_u64(ctx, 1))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 2)), &asts[1]._eq(&Int::from_u64(ctx, 4))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 3)), &asts[1]._eq(&Int::from_u64(ctx, 6))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 4)), &asts[1]._eq(&Int::from_u64(ctx, 2))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 4)), &asts[1]._eq(&Int::from_u64(ctx, 8))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 6)), &asts[1]._eq(&Int::from_u64(ctx, 3))]),
-              &Bool::and(ctx, &[&asts[0]._eq(&Int::from_u64(ctx, 8)), &asts[1]._eq(&Int::from_u64(ctx, 4))]),
-          ]
-      )
-  );
-}
-
-pub fn add_optimizer_constraints(sudoku: &Sudoku, grid: &Vec<Vec<Int<'_>>>, optimizer: &Optimize, ctx: &Context) {
-  add_number_constraints(grid, optimizer, ctx);
+  let two = Int::from_u64(ctx, 2);
+  Bool::or(ctx, &[
+      &asts[0]._eq(&Int::mul(ctx, &[asts[1], &two])),
+      &asts[1]._eq(&Int::mul(ctx, &[asts[0], &two])),
+  ])
+}
+
+fn add_kropki_double_constraint(grid: &[Vec<Int<'_>>], pair: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&kropki_double_ast(grid, pair, ctx));
+}
+
+fn add_kropki_ambiguous_constraint(grid: &[Vec<Int<'_>>], pair: &[Vec<usize>], optimizer: &Optimize, ctx: &Context) {
+  optimizer.assert(&Bool::or(ctx, &[&exact_diff_ast(grid, pair, 1, ctx), &kropki_double_ast(grid, pair, ctx)]));
+}
+
+fn add_consecutive_bar_constraints(grid: &[Vec<Int<'_>>], bars: &[Vec<Vec<usize>>], optimizer: &Optimize, ctx: &Context) {
+  for i in 0..grid.len() {
+      for j in 0..grid.len() {
+          let neighbours = [vec![i + 1, j], vec![i, j + 1]].into_iter().filter(|x| x[0] < grid.len() && x[1] < grid.len());
+          for neighbour in neighbours {
+              let pair = [vec![i, j], neighbour];
+              let consecutive = exact_diff_ast(grid, &pair, 1, ctx);
+              if bars.iter().any(|bar| bar.contains(&pair[0]) && bar.contains(&pair[1])) {
+                  optimizer.assert(&consecutive);
+              } else {
+                  optimizer.assert(&Bool::not(&consecutive));
+              }
+          }
+      }
+  }
+}
+
+fn add_frame_constraint(grid: &[Vec<Int<'_>>], frame: &Frame, optimizer: &Optimize, ctx: &Context) {
+  let squares = frame.side.frame(frame.index, grid.len()).iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+  optimizer.assert(&Int::add(ctx, &squares)._eq(&frame.sum.ast(ctx)));
+}
+
+fn add_skyscraper_constraint(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], skyscraper: &Skyscraper, optimizer: &Optimize, ctx: &Context) {
+  let line = skyscraper.side.line(skyscraper.index, grid.len()).iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+  let (one, zero) = (Int::from_u64(ctx, 1), Int::from_u64(ctx, 0));
+  let mut visible = Vec::new();
+  for k in 0..line.len() {
+      let name = format!("{}skyscraper {} {} visible {k}", sudoku.prefix, skyscraper.side.name(), skyscraper.index);
+      let is_visible = Bool::new_const(ctx, name);
+      let taller = line[..k].iter().map(|&before| line[k].gt(before)).collect::<Vec<_>>();
+      optimizer.assert(&is_visible._eq(&Bool::and(ctx, &taller.iter().collect::<Vec<_>>())));
+      visible.push(is_visible.ite(&one, &zero));
+  }
+  optimizer.assert(&Int::add(ctx, &visible.iter().collect::<Vec<_>>())._eq(&skyscraper.count.ast(ctx)));
+}
+
+/// The first digit seen from the side counts how many squares, itself included, add up to the clue
+fn add_x_sum_constraint(grid: &[Vec<Int<'_>>], x_sum: &XSum, optimizer: &Optimize, ctx: &Context) {
+  let line = x_sum.side.line(x_sum.index, grid.len()).iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+  let zero = Int::from_u64(ctx, 0);
+  let counted = line.iter().enumerate().map(|(k, &square)| line[0].gt(&Int::from_u64(ctx, k as u64)).ite(square, &zero)).collect::<Vec<_>>();
+  optimizer.assert(&Int::add(ctx, &counted.iter().collect::<Vec<_>>())._eq(&x_sum.sum.ast(ctx)));
+}
+
+fn add_indexing_constraint(grid: &[Vec<Int<'_>>], digits: &[u64], cell: &[usize], by_row: bool, optimizer: &Optimize, ctx: &Context) {
+  let (i, j) = (cell[0], cell[1]);
+  for (k, &digit) in digits.iter().enumerate() {
+      let (target, value) = if by_row { (&grid[i][k], digits[j]) } else { (&grid[k][j], digits[i]) };
+      optimizer.assert(&grid[i][j]._eq(&Int::from_u64(ctx, digit)).implies(&target._eq(&Int::from_u64(ctx, value))));
+  }
+}
+
+fn add_sandwich_constraint(grid: &[Vec<Int<'_>>], sandwich: &Sandwich, optimizer: &Optimize, ctx: &Context) {
+  let line = sandwich.side.line(sandwich.index, grid.len()).iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+  let crusts = sandwich.crusts.map(|crust| Int::from_u64(ctx, crust));
+  let (one, zero) = (Int::from_u64(ctx, 1), Int::from_u64(ctx, 0));
+  for crust in &crusts {
+      let hits = line.iter().map(|square| square._eq(crust).ite(&one, &zero)).collect::<Vec<_>>();
+      optimizer.assert(&Int::add(ctx, &hits.iter().collect::<Vec<_>>())._eq(&one));
+  }
+  for p in 0..line.len() {
+      for q in (p + 1)..line.len() {
+          let is_crust = Bool::or(ctx, &[
+              &Bool::and(ctx, &[&line[p]._eq(&crusts[0]), &line[q]._eq(&crusts[1])]),
+              &Bool::and(ctx, &[&line[p]._eq(&crusts[1]), &line[q]._eq(&crusts[0])]),
+          ]);
+          let filling = if p + 1 == q { Int::from_u64(ctx, 0) } else { Int::add(ctx, &line[p + 1..q]) };
+          optimizer.assert(&is_crust.implies(&filling._eq(&sandwich.sum.ast(ctx))));
+      }
+  }
+}
+
+pub fn soft_constraint_ast<'ctx>(sudoku: &Sudoku, grid: &[Vec<Int<'ctx>>], soft: &SoftConstraint, ctx: &'ctx Context) -> Bool<'ctx> {
+  let cells = &soft.cells;
+  match soft.rule {
+      SoftRule::Given(value) => grid[cells[0][0]][cells[0][1]]._eq(&Int::from_u64(ctx, value)),
+      SoftRule::Thermo => increasing_ast(grid, cells, ctx),
+      SoftRule::Arrow => sum_ast(grid, &cells[1..], &cells[..1], ctx),
+      SoftRule::GermanWhisper => Bool::and(ctx, &cells.windows(2).map(
+          |pair| at_least_diff_ast(grid, &pair[0], &pair[1], &sudoku.whisper_difference.ast(ctx), ctx)
+      ).collect::<Vec<_>>().iter().collect::<Vec<_>>()),
+      SoftRule::KropkiAdjacent => exact_diff_ast(grid, cells, 1, ctx),
+      SoftRule::KropkiDouble => kropki_double_ast(grid, cells, ctx),
+      SoftRule::KropkiAmbiguous => Bool::or(ctx, &[&exact_diff_ast(grid, cells, 1, ctx), &kropki_double_ast(grid, cells, ctx)]),
+  }
+}
+
+pub fn add_optimizer_constraints(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], optimizer: &Optimize, ctx: &Context) {
+  add_number_constraints(sudoku, grid, optimizer, ctx);
+  add_unknown_constraints(sudoku, optimizer, ctx);
   add_given_constraints(sudoku, grid, optimizer, ctx);
+  add_state_constraints(sudoku, grid, optimizer, ctx);
+  for region in &sudoku.digit_regions {
+      add_digit_region_constraint(grid, region, optimizer, ctx);
+  }
   if sudoku.horizontal_rule {
       add_horizontal_constraints(grid, optimizer, ctx);
   }
@@ -146,16 +479,66 @@ pub fn add_optimizer_constraints(sudoku: &Sudoku, grid: &Vec<Vec<Int<'_>>>, opti
       add_vertical_constraints(grid, optimizer, ctx);
   }
   if sudoku.nonet_rule {
-      add_nonet_constraints(grid, optimizer, ctx);
+      add_region_constraints(grid, &nonets(grid.len()), optimizer, ctx);
+  }
+  if sudoku.windoku {
+      add_region_constraints(grid, &windows(grid.len()), optimizer, ctx);
+  }
+  if sudoku.disjoint_groups {
+      add_region_constraints(grid, &disjoint_groups(grid.len()), optimizer, ctx);
+  }
+  if sudoku.positive_diagonal {
+      let diagonal = (0..grid.len()).map(|k| &grid[grid.len() - 1 - k][k]).collect::<Vec<_>>();
+      optimizer.assert(&Int::distinct(ctx, &diagonal));
+  }
+  if sudoku.negative_diagonal {
+      let diagonal = (0..grid.len()).map(|k| &grid[k][k]).collect::<Vec<_>>();
+      optimizer.assert(&Int::distinct(ctx, &diagonal));
   }
   if !sudoku.offset.is_empty() {
-      add_offset_constraint(grid, &sudoku.offset, optimizer);
+      add_offset_constraint(grid, &sudoku.offset, &OffsetRelation::NotEqual, optimizer, ctx);
+  }
+  for group in &sudoku.offset_groups {
+      add_offset_constraint(grid, &group.offsets, &group.relation, optimizer, ctx);
+  }
+  if sudoku.taxicab {
+      add_taxicab_constraints(sudoku, grid, optimizer, ctx);
+  }
+  if sudoku.non_consecutive {
+      let orthogonal = ORTHOGONAL.iter().map(|offset| offset.to_vec()).collect::<Vec<_>>();
+      add_offset_constraint(grid, &orthogonal, &OffsetRelation::NotConsecutive, optimizer, ctx);
+  }
+  if !sudoku.odd_shading.is_empty() {
+      add_odd_shading_constraints(sudoku, grid, optimizer, ctx);
+  }
+  add_parity_constraints(sudoku, grid, optimizer, ctx);
+  for quadruple in &sudoku.quadruples {
+      add_quadruple_constraint(grid, quadruple, optimizer, ctx);
+  }
+  for mask in &sudoku.masks {
+      add_mask_constraint(sudoku, grid, mask, optimizer, ctx);
+  }
+  for (i, cage) in sudoku.killer_cages.iter().enumerate() {
+      add_cage_constraint(sudoku, grid, i, cage, optimizer, ctx);
+  }
+  if sudoku.distinct_cage_sums && sudoku.killer_cages.len() > 1 {
+      let sums = (0..sudoku.killer_cages.len()).map(|i| cage_sum(sudoku, i, ctx)).collect::<Vec<_>>();
+      optimizer.assert(&Int::distinct(ctx, &sums.iter().collect::<Vec<_>>()));
+  }
+  if sudoku.cage_anti_adjacency {
+      add_cage_anti_adjacency_constraints(sudoku, grid, optimizer);
+  }
+  for order in &sudoku.line_order {
+      add_line_order_constraint(sudoku, grid, order, optimizer, ctx);
+  }
+  for count in &sudoku.digit_counts {
+      add_digit_count_constraint(grid, count, optimizer, ctx);
   }
   for squares in &sudoku.thermo {
-      add_increasing_constraint(grid, squares, optimizer);
+      add_increasing_constraint(grid, squares, optimizer, ctx);
   }
-  for squares in &sudoku.arrow {
-      add_sum_constraint(grid, &squares[1..], &squares[0], optimizer, ctx);
+  for arrow in &sudoku.arrow {
+      add_sum_constraint(grid, &arrow.cells[arrow.pill..], &arrow.cells[..arrow.pill], arrow.distinct, optimizer, ctx);
   }
   for kropki in &sudoku.kropki_adjacent {
       add_exact_diff_constraint(grid, kropki, 1, optimizer, ctx);
@@ -163,10 +546,49 @@ pub fn add_optimizer_constraints(sudoku: &Sudoku, grid: &Vec<Vec<Int<'_>>>, opti
   for kropki in &sudoku.kropki_double {
       add_kropki_double_constraint(grid, kropki, optimizer, ctx);
   }
+  for kropki in &sudoku.kropki_ambiguous {
+      add_kropki_ambiguous_constraint(grid, kropki, optimizer, ctx);
+  }
+  if let Some(bars) = &sudoku.consecutive_bars {
+      add_consecutive_bar_constraints(grid, bars, optimizer, ctx);
+  }
+  for renban in &sudoku.renban {
+      add_renban_constraint(grid, renban, optimizer, ctx);
+  }
+  for lockout in &sudoku.lockout_lines {
+      add_lockout_constraint(grid, lockout, optimizer, ctx);
+  }
+  for entropic in &sudoku.entropic_lines {
+      add_entropic_constraint(sudoku, grid, entropic, optimizer, ctx);
+  }
+  for modular in &sudoku.modular_lines {
+      add_modular_constraint(grid, modular, optimizer, ctx);
+  }
+  for line in &sudoku.region_sum_lines {
+      add_region_sum_constraint(grid, line, optimizer, ctx);
+  }
   for whisper in &sudoku.german_whispers {
       for i in 0..whisper.len() - 1 {
           let pair = [&whisper[i], &whisper[i + 1]];
-          add_at_least_diff_constraint(grid, &pair, 5, optimizer, ctx);
+          add_at_least_diff_constraint(grid, &pair, &sudoku.whisper_difference.ast(ctx), optimizer, ctx);
       }
   }
+  for frame in &sudoku.frame {
+      add_frame_constraint(grid, frame, optimizer, ctx);
+  }
+  for skyscraper in &sudoku.skyscraper {
+      add_skyscraper_constraint(sudoku, grid, skyscraper, optimizer, ctx);
+  }
+  for x_sum in &sudoku.x_sum {
+      add_x_sum_constraint(grid, x_sum, optimizer, ctx);
+  }
+  for cell in &sudoku.row_indexing {
+      add_indexing_constraint(grid, &sudoku.digits, cell, true, optimizer, ctx);
+  }
+  for cell in &sudoku.column_indexing {
+      add_indexing_constraint(grid, &sudoku.digits, cell, false, optimizer, ctx);
+  }
+  for sandwich in &sudoku.sandwich {
+      add_sandwich_constraint(grid, sandwich, optimizer, ctx);
+  }
 }
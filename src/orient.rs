@@ -0,0 +1,55 @@
+use z3::{Context, Solver};
+use z3::ast::Int;
+
+use crate::{count_up_to, Sudoku};
+use crate::solver::add_solver_constraints;
+
+/// Every combination of orientations is checked, so only a handful of lines can be oriented
+const MAX_LINES: usize = 12;
+
+fn orient(sudoku: &Sudoku, mask: u32) -> Sudoku {
+    let mut oriented = sudoku.clone();
+    for (i, line) in oriented.thermo.iter_mut().chain(oriented.arrow.iter_mut().map(|arrow| &mut arrow.cells)).enumerate() {
+        if mask & (1 << i) != 0 {
+            line.reverse();
+        }
+    }
+    oriented
+}
+
+fn print_orientation(sudoku: &Sudoku) {
+    for (i, thermo) in sudoku.thermo.iter().enumerate() {
+        println!("  Thermo {i}: bulb at Row {} Column {}", thermo[0][0], thermo[0][1]);
+    }
+    for (i, arrow) in sudoku.arrow.iter().enumerate() {
+        println!("  Arrow {i}: circle at Row {} Column {}", arrow.cells[0][0], arrow.cells[0][1]);
+    }
+}
+
+pub fn orient_lines(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], ctx: &Context) {
+    let lines = sudoku.thermo.len() + sudoku.arrow.len();
+    if lines == 0 {
+        println!("No thermos or arrows to orient.");
+        return;
+    }
+    if lines > MAX_LINES {
+        println!("Too many thermos and arrows to orient ({lines}), at most {MAX_LINES} are supported.");
+        return;
+    }
+
+    let mut unique = Vec::new();
+    for mask in 0..(1u32 << lines) {
+        let oriented = orient(sudoku, mask);
+        let solver = Solver::new(ctx);
+        add_solver_constraints(&oriented, grid, &solver, ctx);
+        if count_up_to(grid, &solver, ctx, 2) == 1 {
+            unique.push(oriented);
+        }
+    }
+
+    println!("{} of {} orientation sets give a unique sudoku.", unique.len(), 1u32 << lines);
+    for (i, oriented) in unique.iter().enumerate() {
+        println!("Unique orientation set {}:", i + 1);
+        print_orientation(oriented);
+    }
+}
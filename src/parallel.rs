@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::thread;
+
+use z3::{Config, Context};
+
+use crate::Sudoku;
+use crate::session::Session;
+
+/// Every combination of values of the split squares, one subproblem each
+fn cubes(sudoku: &Sudoku, cells: &[(usize, usize)]) -> Vec<Vec<u64>> {
+    let mut cubes = vec![Vec::new()];
+    for _ in cells {
+        cubes = cubes.into_iter().flat_map(|cube: Vec<u64>| sudoku.digits.iter().map(move |&digit| {
+            let mut cube = cube.clone();
+            cube.push(digit);
+            cube
+        })).collect();
+    }
+    cubes
+}
+
+/// Counts solutions up to just past the limit by splitting on the values of the given squares,
+/// each thread solving subproblems in its own context
+pub fn count_parallel(sudoku: &Sudoku, cells: &[(usize, usize)], limit: u32) -> u32 {
+    let cubes = cubes(sudoku, cells);
+    let next = AtomicUsize::new(0);
+    let total = AtomicU32::new(0);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(cubes.len());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let config = Config::new();
+                let ctx = Context::new(&config);
+                let session = Session::new(sudoku, &ctx);
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= cubes.len() || total.load(Ordering::Relaxed) > limit {
+                        break;
+                    }
+                    let fixed = cells.iter().copied().zip(cubes[index].iter().copied()).collect::<Vec<_>>();
+                    let count = session.with_fixed(&fixed, |session| session.count(limit + 1));
+                    total.fetch_add(count, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    total.into_inner()
+}
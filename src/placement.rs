@@ -0,0 +1,54 @@
+use z3::{Context, SatResult, Solver};
+use z3::ast::{Ast, Bool, Int};
+
+use crate::{count_up_to, Sudoku};
+use crate::solver::add_solver_constraints;
+
+fn print_assignment(sudoku: &Sudoku, assignment: &[u64]) {
+    let squares = sudoku.unknown_givens.iter().zip(assignment).map(
+        |(cell, digit)| format!("Row {} Column {} = {digit}", cell[0], cell[1])
+    ).collect::<Vec<_>>();
+    println!("  {}", squares.join(", "));
+}
+
+pub fn place_unknown_givens(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], ctx: &Context, limit: u32) {
+    if sudoku.unknown_givens.is_empty() {
+        println!("No unknown givens to place.");
+        return;
+    }
+    let squares = sudoku.unknown_givens.iter().map(|cell| &grid[cell[0]][cell[1]]).collect::<Vec<_>>();
+    let solver = Solver::new(ctx);
+    add_solver_constraints(sudoku, grid, &solver, ctx);
+
+    let mut checked = 0;
+    let mut working = Vec::new();
+    while (working.len() as u32) < limit {
+        let assignment = match solver.check() {
+            SatResult::Sat => {
+                let model = solver.get_model().unwrap();
+                squares.iter().map(|square| model.get_const_interp(*square).unwrap().as_u64().unwrap()).collect::<Vec<_>>()
+            }
+            SatResult::Unsat => break,
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        };
+        let fixed = squares.iter().zip(&assignment).map(|(square, &digit)| square._eq(&Int::from_u64(ctx, digit))).collect::<Vec<_>>();
+        checked += 1;
+        solver.push();
+        for square in &fixed {
+            solver.assert(square);
+        }
+        if count_up_to(grid, &solver, ctx, 2) == 1 {
+            working.push(assignment);
+        }
+        solver.pop(1);
+        solver.assert(&Bool::not(&Bool::and(ctx, &fixed.iter().collect::<Vec<_>>())));
+    }
+
+    println!("{} of {checked} checked placements give a unique sudoku.", working.len());
+    for assignment in &working {
+        print_assignment(sudoku, assignment);
+    }
+    if working.len() as u32 == limit {
+        println!("Stopped after {limit} placements, increase max_sudoku to find more.");
+    }
+}
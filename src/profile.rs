@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+use z3::{Context, Params, SatResult, Solver};
+use z3::ast::Int;
+
+use crate::Sudoku;
+use crate::solver::add_solver_constraints;
+
+/// A kind of constraint that can be switched off, with how many constraints of it the sudoku has
+struct Family {
+    name: &'static str,
+    count: usize,
+    remove: fn(&mut Sudoku),
+}
+
+fn families(sudoku: &Sudoku) -> Vec<Family> {
+    let families = [
+        Family {
+            name: "givens",
+            count: sudoku.given.iter().flatten().filter(|given| given.is_some()).count(),
+            remove: |s| s.given.iter_mut().flatten().for_each(|given| *given = None),
+        },
+        Family {
+            name: "state",
+            count: sudoku.state.filled.iter().flatten().filter(|filled| filled.is_some()).count() + sudoku.state.pencilmarks.len(),
+            remove: |s| s.state = Default::default(),
+        },
+        Family { name: "digit regions", count: sudoku.digit_regions.len(), remove: |s| s.digit_regions.clear() },
+        Family { name: "offsets", count: sudoku.offset.len(), remove: |s| s.offset.clear() },
+        Family { name: "offset groups", count: sudoku.offset_groups.len(), remove: |s| s.offset_groups.clear() },
+        Family { name: "odd shading", count: sudoku.odd_shading.len(), remove: |s| s.odd_shading.clear() },
+        Family {
+            name: "odd/even cells",
+            count: sudoku.odd_cells.len() + sudoku.even_cells.len(),
+            remove: |s| {
+                s.odd_cells.clear();
+                s.even_cells.clear();
+            },
+        },
+        Family { name: "quadruples", count: sudoku.quadruples.len(), remove: |s| s.quadruples.clear() },
+        Family { name: "killer cages", count: sudoku.killer_cages.len(), remove: |s| s.killer_cages.clear() },
+        Family { name: "distinct cage sums", count: sudoku.distinct_cage_sums as usize, remove: |s| s.distinct_cage_sums = false },
+        Family { name: "cage anti-adjacency", count: sudoku.cage_anti_adjacency as usize, remove: |s| s.cage_anti_adjacency = false },
+        Family { name: "digit counts", count: sudoku.digit_counts.len(), remove: |s| s.digit_counts.clear() },
+        Family { name: "masks", count: sudoku.masks.len(), remove: |s| s.masks.clear() },
+        Family { name: "windoku", count: sudoku.windoku as usize, remove: |s| s.windoku = false },
+        Family { name: "disjoint groups", count: sudoku.disjoint_groups as usize, remove: |s| s.disjoint_groups = false },
+        Family {
+            name: "diagonals",
+            count: sudoku.positive_diagonal as usize + sudoku.negative_diagonal as usize,
+            remove: |s| (s.positive_diagonal, s.negative_diagonal) = (false, false),
+        },
+        Family { name: "taxicab", count: sudoku.taxicab as usize, remove: |s| s.taxicab = false },
+        Family { name: "non-consecutive", count: sudoku.non_consecutive as usize, remove: |s| s.non_consecutive = false },
+        Family { name: "thermo", count: sudoku.thermo.len(), remove: |s| s.thermo.clear() },
+        Family { name: "arrow", count: sudoku.arrow.len(), remove: |s| s.arrow.clear() },
+        Family { name: "kropki adjacent", count: sudoku.kropki_adjacent.len(), remove: |s| s.kropki_adjacent.clear() },
+        Family { name: "kropki double", count: sudoku.kropki_double.len(), remove: |s| s.kropki_double.clear() },
+        Family { name: "kropki ambiguous", count: sudoku.kropki_ambiguous.len(), remove: |s| s.kropki_ambiguous.clear() },
+        Family {
+            name: "consecutive bars",
+            count: sudoku.consecutive_bars.as_ref().map_or(0, |bars| bars.len().max(1)),
+            remove: |s| s.consecutive_bars = None,
+        },
+        Family { name: "german whispers", count: sudoku.german_whispers.len(), remove: |s| s.german_whispers.clear() },
+        Family { name: "renban", count: sudoku.renban.len(), remove: |s| s.renban.clear() },
+        Family { name: "lockout lines", count: sudoku.lockout_lines.len(), remove: |s| s.lockout_lines.clear() },
+        Family { name: "entropic lines", count: sudoku.entropic_lines.len(), remove: |s| s.entropic_lines.clear() },
+        Family { name: "modular lines", count: sudoku.modular_lines.len(), remove: |s| s.modular_lines.clear() },
+        Family { name: "region sum lines", count: sudoku.region_sum_lines.len(), remove: |s| s.region_sum_lines.clear() },
+        Family { name: "row indexing", count: sudoku.row_indexing.len(), remove: |s| s.row_indexing.clear() },
+        Family { name: "column indexing", count: sudoku.column_indexing.len(), remove: |s| s.column_indexing.clear() },
+        Family { name: "sandwich", count: sudoku.sandwich.len(), remove: |s| s.sandwich.clear() },
+        Family { name: "line order", count: sudoku.line_order.len(), remove: |s| s.line_order.clear() },
+        Family { name: "frame", count: sudoku.frame.len(), remove: |s| s.frame.clear() },
+        Family { name: "skyscraper", count: sudoku.skyscraper.len(), remove: |s| s.skyscraper.clear() },
+        Family { name: "X-sum", count: sudoku.x_sum.len(), remove: |s| s.x_sum.clear() },
+    ];
+    families.into_iter().filter(|family| family.count > 0).collect()
+}
+
+/// Median time of finding a solution over the trials with a fresh solver each time, None if a run hits the timeout
+fn solve_time(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], ctx: &Context, trials: u32, timeout: Option<Duration>) -> Option<Duration> {
+    let mut times = Vec::new();
+    for _ in 0..trials.max(1) {
+        let solver = Solver::new(ctx);
+        if let Some(timeout) = timeout {
+            let mut params = Params::new(ctx);
+            params.set_u32("timeout", timeout.as_millis() as u32);
+            solver.set_params(&params);
+        }
+        add_solver_constraints(sudoku, grid, &solver, ctx);
+        let start = Instant::now();
+        if solver.check() == SatResult::Unknown {
+            return None;
+        }
+        times.push(start.elapsed());
+    }
+    times.sort();
+    Some(times[times.len() / 2])
+}
+
+/// Times the sudoku with each constraint family removed in turn and reports how much faster it gets
+pub fn profile_families(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], ctx: &Context, trials: u32) {
+    let Some(baseline) = solve_time(sudoku, grid, ctx, trials, None) else {
+        panic!("Solver returned unknown!");
+    };
+    let timeout = (baseline * 20).max(Duration::from_secs(1));
+    println!("All constraints: {:.1} ms", baseline.as_secs_f64() * 1000.0);
+    println!("{:<18} {:>6} {:>12} {:>9}", "Without", "Count", "Time", "Speedup");
+    for Family { name, count, remove } in families(sudoku) {
+        let mut reduced = sudoku.clone();
+        remove(&mut reduced);
+        match solve_time(&reduced, grid, ctx, trials, Some(timeout)) {
+            Some(time) => {
+                let speedup = baseline.as_secs_f64() / time.as_secs_f64().max(1e-9);
+                println!("{name:<18} {count:>6} {:>9.1} ms {speedup:>8.1}x", time.as_secs_f64() * 1000.0);
+            }
+            None => println!("{name:<18} {count:>6} {:>12} {:>9}", format!(">{} ms", timeout.as_millis()), "slower"),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use z3::{Context, SatResult};
+use z3::ast::{Ast, Bool, Int};
+
+use crate::{nonets, Sudoku};
+use crate::objective::parse_linear;
+use crate::session::Session;
+
+/// Squares of a line named like r1, c4, b0, diag (top left to bottom right) or antidiag (bottom left to top right)
+fn line(name: &str, size: usize) -> Option<Vec<(usize, usize)>> {
+    match name {
+        "diag" => return Some((0..size).map(|k| (k, k)).collect()),
+        "antidiag" => return Some((0..size).map(|k| (size - 1 - k, k)).collect()),
+        _ => {}
+    }
+    let index = name.get(1..)?.parse::<usize>().ok().filter(|&index| index < size)?;
+    match name.chars().next()? {
+        'r' => Some((0..size).map(|j| (index, j)).collect()),
+        'c' => Some((0..size).map(|i| (i, index)).collect()),
+        'b' => nonets(size).into_iter().nth(index),
+        _ => None,
+    }
+}
+
+/// Parses an expression over the solution: sum(line), product(line), concat(line) or a bare line read as one
+/// number, and otherwise a linear expression over squares such as `r0c0 + 2*r1c1`
+pub fn parse_projection<'ctx>(expression: &str, sudoku: &Sudoku, grid: &[Vec<Int<'ctx>>], ctx: &'ctx Context) -> Result<Int<'ctx>, String> {
+    let expression = expression.trim();
+    let (function, name) = match expression.split_once('(') {
+        Some((function, rest)) => (function.trim(), rest.strip_suffix(')').ok_or_else(|| format!("Missing ) in {expression}"))?.trim()),
+        None => ("concat", expression),
+    };
+    let Some(cells) = line(name, grid.len()) else {
+        return parse_linear(expression, grid, ctx);
+    };
+    let squares = cells.iter().map(|&(i, j)| &grid[i][j]).collect::<Vec<_>>();
+    match function {
+        "sum" => Ok(Int::add(ctx, &squares)),
+        "product" => Ok(Int::mul(ctx, &squares)),
+        "concat" => {
+            let base = Int::from_u64(ctx, sudoku.digits.last().map_or(10, |&digit| (digit + 1).max(10)));
+            Ok(squares.into_iter().fold(Int::from_u64(ctx, 0), |number, square| Int::add(ctx, &[&Int::mul(ctx, &[&number, &base]), square])))
+        }
+        _ => Err(format!("Unknown function {function}, use sum, product or concat")),
+    }
+}
+
+/// Every value the expression takes across the solutions, stopping after the limit. Each value found is
+/// blocked on the expression alone, so solutions agreeing on it are never enumerated.
+pub fn project(session: &Session, expression: &Int, limit: u32) -> Vec<i64> {
+    let mut values = Vec::new();
+    session.solver.push();
+    while values.len() < limit as usize {
+        match session.solver.check() {
+            SatResult::Sat => {
+                let value = session.solver.get_model().unwrap().eval(expression, true).unwrap();
+                session.solver.assert(&Bool::not(&expression._eq(&value)));
+                values.push(value.as_i64().unwrap());
+            }
+            SatResult::Unsat => break,
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        }
+    }
+    session.solver.pop(1);
+    values.sort();
+    values
+}
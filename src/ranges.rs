@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use crate::{CageRelation, Clue, Sudoku};
+
+/// Smallest and largest totals of `count` digits, distinct or not
+fn extreme_sums(digits: &[u64], count: usize, distinct: bool) -> (u64, u64) {
+    if distinct {
+        (digits.iter().take(count).sum(), digits.iter().rev().take(count).sum())
+    } else {
+        (digits[0] * count as u64, digits[digits.len() - 1] * count as u64)
+    }
+}
+
+/// Bounds on single squares that follow from killer cage sums and the arrows with one square in their circle,
+/// before any solving, such as "r1c1 ∈ 1..3 because cage 6(3)". Only bounds tighter than the digits are listed.
+pub fn range_hints(sudoku: &Sudoku) -> Vec<String> {
+    let digits = &sudoku.digits;
+    let (lowest, highest) = (digits[0], digits[digits.len() - 1]);
+    let mut ranges: BTreeMap<(usize, usize), (u64, u64, Vec<String>)> = BTreeMap::new();
+    let mut narrow = |cell: &[usize], low: u64, high: u64, reason: String| {
+        if low > lowest || high < highest {
+            let range = ranges.entry((cell[0], cell[1])).or_insert((lowest, highest, Vec::new()));
+            range.0 = range.0.max(low);
+            range.1 = range.1.min(high);
+            range.2.push(reason);
+        }
+    };
+    // The clues of mystery killers are not the sums themselves
+    let mystery = sudoku.cage_sum_modulo.is_some() || sudoku.cage_sum_offset.is_some();
+    for cage in sudoku.killer_cages.iter().filter(|_| !mystery) {
+        let (Some(Clue::Known(sum)), CageRelation::Equal) = (&cage.sum, cage.relation) else { continue };
+        let sum = *sum;
+        let others = extreme_sums(digits, cage.cells.len().saturating_sub(1), true);
+        let (low, high) = (sum.saturating_sub(others.1).max(lowest), sum.saturating_sub(others.0).min(highest));
+        for cell in &cage.cells {
+            narrow(cell, low, high, format!("cage {sum}({})", cage.cells.len()));
+        }
+    }
+    for (index, arrow) in sudoku.arrow.iter().enumerate().filter(|(_, arrow)| arrow.pill == 1) {
+        let shaft = &arrow.cells[1..];
+        let (least, _) = extreme_sums(digits, shaft.len(), arrow.distinct);
+        narrow(&arrow.cells[0], least.max(lowest), highest, format!("arrow {index}"));
+        let (others, _) = extreme_sums(digits, shaft.len().saturating_sub(1), arrow.distinct);
+        for cell in shaft {
+            narrow(cell, lowest, highest.saturating_sub(others).max(lowest), format!("arrow {index}"));
+        }
+    }
+    ranges.into_iter().map(|((i, j), (low, high, reasons))| {
+        format!("r{i}c{j} ∈ {low}..{high} because {}", reasons.join(" and "))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classic_sudoku, Arrow, Cage};
+
+    const EMPTY: [&str; 9] = ["........."; 9];
+
+    #[test]
+    fn cage_bounds() {
+        let mut sudoku = classic_sudoku(&EMPTY);
+        sudoku.killer_cages = vec![
+            Cage { cells: vec![vec![0, 0], vec![0, 1], vec![0, 2]], sum: Some(Clue::Known(6)), relation: CageRelation::Equal },
+            Cage { cells: vec![vec![8, 7], vec![8, 8]], sum: Some(Clue::Known(17)), relation: CageRelation::Equal },
+            Cage { cells: vec![vec![4, 4], vec![4, 5]], sum: Some(Clue::Known(10)), relation: CageRelation::Equal },
+            Cage { cells: vec![vec![2, 0], vec![2, 1]], sum: Some(Clue::Known(4)), relation: CageRelation::LessThan },
+        ];
+        assert_eq!(range_hints(&sudoku), [
+            "r0c0 ∈ 1..3 because cage 6(3)", "r0c1 ∈ 1..3 because cage 6(3)", "r0c2 ∈ 1..3 because cage 6(3)",
+            "r8c7 ∈ 8..9 because cage 17(2)", "r8c8 ∈ 8..9 because cage 17(2)",
+        ]);
+    }
+
+    #[test]
+    fn arrow_bounds() {
+        let mut sudoku = classic_sudoku(&EMPTY);
+        sudoku.arrow = vec![Arrow { cells: vec![vec![0, 0], vec![0, 1], vec![0, 2], vec![0, 3]], pill: 1, distinct: true }];
+        assert_eq!(range_hints(&sudoku), [
+            "r0c0 ∈ 6..9 because arrow 0", "r0c1 ∈ 1..6 because arrow 0", "r0c2 ∈ 1..6 because arrow 0", "r0c3 ∈ 1..6 because arrow 0",
+        ]);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use crate::{box_shape, MaskRule, OffsetRelation, Reading, Side, SoftRule, Sudoku};
+use crate::normalize::{normalize, sudoku_to_json};
+use crate::rng::Rng;
+
+/// One of the eight symmetries of a square grid: an optional transposition followed by optional reversals of
+/// the rows and the columns
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Symmetry {
+    transpose: bool,
+    reverse_rows: bool,
+    reverse_columns: bool,
+}
+
+impl Symmetry {
+    fn all() -> Vec<Symmetry> {
+        (0..8).map(|k| Symmetry { transpose: k & 4 != 0, reverse_rows: k & 2 != 0, reverse_columns: k & 1 != 0 }).collect()
+    }
+
+    /// The symmetries that take boxes to boxes, the identity first. Transposing only works for square boxes.
+    fn of_boxes(size: usize) -> Vec<Symmetry> {
+        let (box_height, box_width) = box_shape(size);
+        Symmetry::all().into_iter().filter(|symmetry| !symmetry.transpose || box_height == box_width).collect()
+    }
+
+    fn name(self) -> &'static str {
+        match (self.transpose, self.reverse_rows, self.reverse_columns) {
+            (false, false, false) => "kept as it is",
+            (false, false, true) => "mirrored horizontally",
+            (false, true, false) => "mirrored vertically",
+            (false, true, true) => "rotated 180 degrees",
+            (true, false, false) => "reflected in the main diagonal",
+            (true, false, true) => "rotated 90 degrees clockwise",
+            (true, true, false) => "rotated 90 degrees anticlockwise",
+            (true, true, true) => "reflected in the anti-diagonal",
+        }
+    }
+
+    fn square(self, (i, j): (usize, usize), size: usize) -> (usize, usize) {
+        let (i, j) = if self.transpose { (j, i) } else { (i, j) };
+        (if self.reverse_rows { size - 1 - i } else { i }, if self.reverse_columns { size - 1 - j } else { j })
+    }
+
+    fn cell(self, cell: &[usize], size: usize) -> Vec<usize> {
+        let (i, j) = self.square((cell[0], cell[1]), size);
+        vec![i, j]
+    }
+
+    fn cells(self, cells: &[Vec<usize>], size: usize) -> Vec<Vec<usize>> {
+        cells.iter().map(|cell| self.cell(cell, size)).collect()
+    }
+
+    fn lines(self, lines: &[Vec<Vec<usize>>], size: usize) -> Vec<Vec<Vec<usize>>> {
+        lines.iter().map(|line| self.cells(line, size)).collect()
+    }
+
+    fn offset(self, offset: &[i32]) -> Vec<i32> {
+        let (di, dj) = if self.transpose { (offset[1], offset[0]) } else { (offset[0], offset[1]) };
+        vec![if self.reverse_rows { -di } else { di }, if self.reverse_columns { -dj } else { dj }]
+    }
+
+    fn grid<T: Clone>(self, grid: &[Vec<T>]) -> Vec<Vec<T>> {
+        let size = grid.len();
+        let mut moved = grid.to_vec();
+        for (i, row) in grid.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let (a, b) = self.square((i, j), size);
+                moved[a][b] = value.clone();
+            }
+        }
+        moved
+    }
+
+    /// The side and index of the image of a row or column, read in the same direction as before
+    fn reading(self, side: Side, index: usize, size: usize) -> (Side, usize) {
+        let line = side.line(index, size);
+        let (first, second) = (self.square(line[0], size), self.square(line[1], size));
+        match (first.0 == second.0, first.1 == 0, first.0 == 0) {
+            (true, true, _) => (Side::Left, first.0),
+            (true, false, _) => (Side::Right, first.0),
+            (false, _, true) => (Side::Top, first.1),
+            (false, _, false) => (Side::Bottom, first.1),
+        }
+    }
+}
+
+/// Names of the rules of the sudoku that depend on the values of the digits and not just on which are equal,
+/// so the digits cannot be permuted
+pub fn value_rules(sudoku: &Sudoku) -> Vec<&'static str> {
+    let offset_values = sudoku.offset_groups.iter().any(|group| !matches!(group.relation, OffsetRelation::NotEqual));
+    let mask_values = sudoku.masks.iter().any(|mask| !matches!(mask.rule, MaskRule::Distinct));
+    let soft_values = sudoku.soft.iter().any(|soft| !matches!(soft.rule, SoftRule::Given(_)));
+    [
+        (!sudoku.thermo.is_empty(), "thermo"),
+        (!sudoku.arrow.is_empty(), "arrow"),
+        (!sudoku.kropki_adjacent.is_empty() || !sudoku.kropki_double.is_empty() || !sudoku.kropki_ambiguous.is_empty(), "kropki"),
+        (sudoku.consecutive_bars.is_some(), "consecutive bars"),
+        (!sudoku.german_whispers.is_empty(), "german whispers"),
+        (!sudoku.renban.is_empty(), "renban"),
+        (!sudoku.lockout_lines.is_empty(), "lockout lines"),
+        (!sudoku.entropic_lines.is_empty(), "entropic lines"),
+        (!sudoku.modular_lines.is_empty(), "modular lines"),
+        (!sudoku.region_sum_lines.is_empty(), "region sum lines"),
+        (!sudoku.row_indexing.is_empty() || !sudoku.column_indexing.is_empty(), "indexing"),
+        (!sudoku.sandwich.is_empty(), "sandwich"),
+        (!sudoku.frame.is_empty(), "frame"),
+        (!sudoku.skyscraper.is_empty(), "skyscraper"),
+        (!sudoku.x_sum.is_empty(), "X-sum"),
+        (!sudoku.line_order.is_empty(), "line order"),
+        (!sudoku.odd_shading.is_empty(), "odd shading"),
+        (!sudoku.odd_cells.is_empty() || !sudoku.even_cells.is_empty(), "odd/even cells"),
+        (sudoku.taxicab, "taxicab"),
+        (sudoku.non_consecutive, "non-consecutive"),
+        (offset_values, "offset group"),
+        (mask_values, "mask"),
+        (sudoku.killer_cages.iter().any(|cage| cage.sum.is_some()) || sudoku.distinct_cage_sums, "killer cage sum"),
+        (soft_values, "soft constraint"),
+    ].into_iter().filter(|(present, _)| *present).map(|(_, name)| name).collect()
+}
+
+/// Names of the rules of the sudoku that tie digits to positions, so the grid cannot be turned or mirrored
+fn position_rules(sudoku: &Sudoku) -> Vec<&'static str> {
+    [
+        (!sudoku.row_indexing.is_empty() || !sudoku.column_indexing.is_empty(), "indexing"),
+    ].into_iter().filter(|(present, _)| *present).map(|(_, name)| name).collect()
+}
+
+fn apply_digits(sudoku: &mut Sudoku, map: &HashMap<u64, u64>) {
+    let relabel = |digit: &mut u64| *digit = map[digit];
+    for row in sudoku.given.iter_mut().chain(sudoku.state.filled.iter_mut()) {
+        row.iter_mut().flatten().for_each(relabel);
+    }
+    for mark in &mut sudoku.state.pencilmarks {
+        mark.digits.iter_mut().for_each(relabel);
+        mark.digits.sort();
+    }
+    for region in &mut sudoku.digit_regions {
+        region.digits.iter_mut().for_each(relabel);
+        region.digits.sort();
+    }
+    for quadruple in &mut sudoku.quadruples {
+        quadruple.digits.iter_mut().for_each(relabel);
+        quadruple.digits.sort();
+    }
+    for count in &mut sudoku.digit_counts {
+        relabel(&mut count.digit);
+    }
+    for soft in &mut sudoku.soft {
+        if let SoftRule::Given(given) = &mut soft.rule {
+            relabel(given);
+        }
+    }
+}
+
+fn apply_symmetry(sudoku: &mut Sudoku, symmetry: Symmetry) {
+    let size = sudoku.size();
+    let negative_diagonal = symmetry.square((0, 0), size);
+    if negative_diagonal.0 != negative_diagonal.1 {
+        std::mem::swap(&mut sudoku.positive_diagonal, &mut sudoku.negative_diagonal);
+    }
+    sudoku.given = symmetry.grid(&sudoku.given);
+    if !sudoku.state.filled.is_empty() {
+        sudoku.state.filled = symmetry.grid(&sudoku.state.filled);
+    }
+    for mark in &mut sudoku.state.pencilmarks {
+        mark.cell = symmetry.cell(&mark.cell, size);
+    }
+    sudoku.unknown_givens = symmetry.cells(&sudoku.unknown_givens, size);
+    sudoku.fog = symmetry.cells(&sudoku.fog, size);
+    sudoku.odd_shading = symmetry.cells(&sudoku.odd_shading, size);
+    sudoku.odd_cells = symmetry.cells(&sudoku.odd_cells, size);
+    sudoku.even_cells = symmetry.cells(&sudoku.even_cells, size);
+    sudoku.offset = sudoku.offset.iter().map(|offset| symmetry.offset(offset)).collect();
+    for group in &mut sudoku.offset_groups {
+        group.offsets = group.offsets.iter().map(|offset| symmetry.offset(offset)).collect();
+    }
+    sudoku.thermo = symmetry.lines(&sudoku.thermo, size);
+    sudoku.kropki_adjacent = symmetry.lines(&sudoku.kropki_adjacent, size);
+    sudoku.kropki_double = symmetry.lines(&sudoku.kropki_double, size);
+    sudoku.kropki_ambiguous = symmetry.lines(&sudoku.kropki_ambiguous, size);
+    sudoku.german_whispers = symmetry.lines(&sudoku.german_whispers, size);
+    sudoku.renban = symmetry.lines(&sudoku.renban, size);
+    sudoku.lockout_lines = symmetry.lines(&sudoku.lockout_lines, size);
+    sudoku.entropic_lines = symmetry.lines(&sudoku.entropic_lines, size);
+    sudoku.modular_lines = symmetry.lines(&sudoku.modular_lines, size);
+    sudoku.region_sum_lines = symmetry.lines(&sudoku.region_sum_lines, size);
+    if let Some(bars) = &sudoku.consecutive_bars {
+        sudoku.consecutive_bars = Some(symmetry.lines(bars, size));
+    }
+    for arrow in &mut sudoku.arrow {
+        arrow.cells = symmetry.cells(&arrow.cells, size);
+    }
+    for region in &mut sudoku.digit_regions {
+        region.cells = symmetry.cells(&region.cells, size);
+    }
+    for quadruple in &mut sudoku.quadruples {
+        quadruple.cell = symmetry.cells(&quadruple.cells(), size).into_iter().min().unwrap();
+    }
+    for soft in &mut sudoku.soft {
+        soft.cells = symmetry.cells(&soft.cells, size);
+    }
+    for mask in &mut sudoku.masks {
+        mask.cells = symmetry.cells(&mask.cells, size);
+    }
+    for cage in &mut sudoku.killer_cages {
+        cage.cells = symmetry.cells(&cage.cells, size);
+    }
+    for count in &mut sudoku.digit_counts {
+        count.cells = symmetry.cells(&count.cells, size);
+    }
+    for sandwich in &mut sudoku.sandwich {
+        (sandwich.side, sandwich.index) = symmetry.reading(sandwich.side, sandwich.index, size);
+    }
+    for frame in &mut sudoku.frame {
+        (frame.side, frame.index) = symmetry.reading(frame.side, frame.index, size);
+    }
+    for skyscraper in &mut sudoku.skyscraper {
+        (skyscraper.side, skyscraper.index) = symmetry.reading(skyscraper.side, skyscraper.index, size);
+    }
+    for x_sum in &mut sudoku.x_sum {
+        (x_sum.side, x_sum.index) = symmetry.reading(x_sum.side, x_sum.index, size);
+    }
+    let reading = |reading: &Reading| {
+        let (side, index) = symmetry.reading(reading.side, reading.index, size);
+        Reading { side, index }
+    };
+    for order in &mut sudoku.line_order {
+        (order.lesser, order.greater) = (reading(&order.lesser), reading(&order.greater));
+    }
+}
+
+/// An equivalent sudoku with its digits randomly permuted and its grid randomly turned or mirrored, along with
+/// a description of what was done. Digits are kept when a rule depends on their values and the grid when a
+/// rule ties digits to positions, and it is an error if neither can change.
+pub fn relabel(sudoku: &Sudoku, seed: u64) -> Result<(Sudoku, Vec<String>), String> {
+    let mut rng = Rng::new(seed);
+    let mut relabelled = sudoku.clone();
+    let mut changes = Vec::new();
+    let size = sudoku.size();
+
+    let value_rules = value_rules(sudoku);
+    if value_rules.is_empty() {
+        let mut digits = sudoku.digits.clone();
+        for k in (1..digits.len()).rev() {
+            digits.swap(k, (rng.next_u64() % (k as u64 + 1)) as usize);
+        }
+        let map = sudoku.digits.iter().copied().zip(digits.iter().copied()).collect::<HashMap<_, _>>();
+        apply_digits(&mut relabelled, &map);
+        let pairs = sudoku.digits.iter().map(|digit| format!("{digit}->{}", map[digit])).collect::<Vec<_>>();
+        changes.push(format!("Digits relabelled {}", pairs.join(", ")));
+    } else {
+        changes.push(format!("Digits kept, they matter to the {} rules", value_rules.join(", ")));
+    }
+
+    let position_rules = position_rules(sudoku);
+    let symmetries = Symmetry::of_boxes(size)[1..].to_vec();
+    if position_rules.is_empty() {
+        let symmetry = symmetries[(rng.next_u64() % symmetries.len() as u64) as usize];
+        apply_symmetry(&mut relabelled, symmetry);
+        changes.push(format!("Grid {}", symmetry.name()));
+    } else if !value_rules.is_empty() {
+        return Err(format!("the digits matter to the {} rules and the positions to the {} rules", value_rules.join(", "), position_rules.join(", ")));
+    } else {
+        changes.push(format!("Grid kept, positions matter to the {} rules", position_rules.join(", ")));
+    }
+    Ok((relabelled, changes))
+}
+
+/// Relabels the digits in the order they first appear in the givens and then the filled digits, read row by
+/// row, with the digits that never appear keeping their order after them
+fn digits_by_appearance(sudoku: &Sudoku) -> HashMap<u64, u64> {
+    let mut order = Vec::new();
+    for digit in sudoku.given.iter().chain(sudoku.state.filled.iter()).flatten().flatten() {
+        if !order.contains(digit) {
+            order.push(*digit);
+        }
+    }
+    order.extend(sudoku.digits.iter().filter(|digit| !order.contains(digit)).copied().collect::<Vec<_>>());
+    order.into_iter().zip(sudoku.digits.iter().copied()).collect()
+}
+
+/// The same text for every sudoku that relabel could have produced from this one: the smallest normalized
+/// JSON over the allowed turns and mirrors, each with its digits relabelled by first appearance
+pub fn canonical_form(sudoku: &Sudoku) -> String {
+    let relabel_digits = value_rules(sudoku).is_empty();
+    let symmetries = if position_rules(sudoku).is_empty() { Symmetry::of_boxes(sudoku.size()) } else { Symmetry::all()[..1].to_vec() };
+    symmetries.into_iter().map(|symmetry| {
+        let mut moved = sudoku.clone();
+        apply_symmetry(&mut moved, symmetry);
+        if relabel_digits {
+            let map = digits_by_appearance(&moved);
+            apply_digits(&mut moved, &map);
+        }
+        serde_json::to_string(&sudoku_to_json(&normalize(&moved))).unwrap()
+    }).min().unwrap()
+}
+
+/// A short hash of the canonical form, equal for puzzles that are the same up to relabelling
+pub fn fingerprint(sudoku: &Sudoku) -> String {
+    let hash = canonical_form(sudoku).bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic_sudoku;
+
+    const WIKI: [&str; 9] = [
+        "53..7....", "6..195...", ".98....6.", "8...6...3", "4..8.3..1", "7...2...6", ".6....28.", "...419..5", "....8..79",
+    ];
+
+    #[test]
+    fn canonical_form_survives_relabel() {
+        let sudoku = classic_sudoku(&WIKI);
+        let canonical = canonical_form(&sudoku);
+        for seed in 0..8 {
+            let (relabelled, _) = relabel(&sudoku, seed).unwrap();
+            assert_eq!(canonical_form(&relabelled), canonical, "seed {seed}");
+            assert_eq!(fingerprint(&relabelled), fingerprint(&sudoku));
+        }
+    }
+
+    #[test]
+    fn canonical_form_keeps_values_under_value_rules() {
+        let mut sudoku = classic_sudoku(&WIKI);
+        sudoku.thermo = vec![vec![vec![0, 2], vec![0, 3], vec![1, 3]]];
+        let canonical = canonical_form(&sudoku);
+        for seed in 0..8 {
+            let (relabelled, _) = relabel(&sudoku, seed).unwrap();
+            assert_eq!(canonical_form(&relabelled), canonical, "seed {seed}");
+        }
+        let mut changed = sudoku.clone();
+        changed.thermo[0].reverse();
+        assert_ne!(canonical_form(&changed), canonical);
+    }
+}
@@ -0,0 +1,77 @@
+use z3::{Context, SatResult, Solver};
+use z3::ast::Int;
+
+use crate::{block_solution, print_candidate_grid, solution_from_model, Sudoku};
+use crate::estimate::{random_xor, value_bits, PIVOT};
+use crate::rng::Rng;
+
+fn solutions_up_to(grid: &[Vec<Int<'_>>], solver: &Solver, ctx: &Context, limit: u32) -> Vec<Vec<Vec<u64>>> {
+    solver.push();
+    let mut solutions = Vec::new();
+    while solutions.len() < limit as usize {
+        match solver.check() {
+            SatResult::Sat => {
+                let model = solver.get_model().unwrap();
+                solutions.push(solution_from_model(&model, grid));
+                block_solution(&model, grid, solver, ctx);
+            }
+            SatResult::Unsat => break,
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        }
+    }
+    solver.pop(1);
+    solutions
+}
+
+/// Draws distinct solutions close to uniformly, by hashing the solutions into a cell of fewer than PIVOT
+/// with random XOR constraints and picking one of that cell at random
+pub fn sample_solutions(sudoku: &Sudoku, grid: &[Vec<Int<'_>>], solver: &Solver, ctx: &Context, samples: u32, seed: u64) {
+    let mut rng = Rng::new(seed);
+    let mut pool = solutions_up_to(grid, solver, ctx, PIVOT);
+    if pool.is_empty() {
+        println!("Could not find a satisfying sudoku.");
+        return;
+    }
+
+    let mut picked = Vec::new();
+    if pool.len() < PIVOT as usize {
+        println!("Found exactly {} possible sudokus, picking from all of them with seed {seed}...", pool.len());
+        while picked.len() < samples as usize && !pool.is_empty() {
+            let index = (rng.next_u64() % pool.len() as u64) as usize;
+            picked.push(pool.swap_remove(index));
+        }
+    } else {
+        println!("Found at least {PIVOT} sudokus, hashing with seed {seed}...");
+        let bits = value_bits(sudoku, grid, ctx);
+        let mut attempts = 0;
+        while picked.len() < samples as usize && attempts < samples * 10 {
+            attempts += 1;
+            solver.push();
+            let mut cell = Vec::new();
+            for _ in 0..bits.len() {
+                solver.assert(&random_xor(&bits, &mut rng, ctx));
+                cell = solutions_up_to(grid, solver, ctx, PIVOT);
+                if cell.len() < PIVOT as usize {
+                    break;
+                }
+            }
+            solver.pop(1);
+            if cell.is_empty() {
+                continue;
+            }
+            let solution = cell.swap_remove((rng.next_u64() % cell.len() as u64) as usize);
+            if !picked.contains(&solution) {
+                picked.push(solution);
+            }
+        }
+    }
+
+    if picked.len() < samples as usize {
+        println!("Only drew {} distinct sudokus.", picked.len());
+    }
+    for (num, solution) in picked.iter().enumerate() {
+        println!("Sample {}:", num + 1);
+        let candidates = solution.iter().map(|row| row.iter().map(|&digit| vec![digit]).collect()).collect::<Vec<Vec<_>>>();
+        print_candidate_grid(&candidates, &[]);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::logic::Candidates;
+use crate::{disjoint_groups, nonets, windows, Clue, OffsetRelation, Sudoku, ORTHOGONAL};
+
+/// The sudoku as clauses over one boolean per square and digit, true when the square holds the digit.
+/// Literals are variables counted from 1 and negated when false, as in DIMACS.
+pub struct Cnf {
+    size: usize,
+    digits: Vec<u64>,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    fn new(size: usize, digits: &[u64]) -> Cnf {
+        let mut cnf = Cnf { size, digits: digits.to_vec(), clauses: Vec::new() };
+        for i in 0..size {
+            for j in 0..size {
+                cnf.allow((i, j), digits);
+                for a in 0..digits.len() {
+                    for b in (a + 1)..digits.len() {
+                        cnf.clauses.push(vec![-cnf.var((i, j), a), -cnf.var((i, j), b)]);
+                    }
+                }
+            }
+        }
+        cnf
+    }
+
+    pub fn variables(&self) -> usize {
+        self.size * self.size * self.digits.len()
+    }
+
+    fn var(&self, (i, j): (usize, usize), k: usize) -> i32 {
+        ((i * self.size + j) * self.digits.len() + k + 1) as i32
+    }
+
+    /// The square holds one of the digits
+    fn allow(&mut self, square: (usize, usize), allowed: &[u64]) {
+        let clause = self.digits.iter().enumerate().filter(|(_, digit)| allowed.contains(digit)).map(|(k, _)| self.var(square, k)).collect();
+        self.clauses.push(clause);
+    }
+
+    /// No digit appears twice among the squares. When there is a square for every digit, every digit also
+    /// appears somewhere, which adds nothing to the rules but lets propagation find hidden singles.
+    fn distinct(&mut self, squares: &[(usize, usize)]) {
+        for p in 0..squares.len() {
+            for q in (p + 1)..squares.len() {
+                self.relate(squares[p], squares[q], |a, b| a != b);
+            }
+        }
+        if squares.len() == self.digits.len() {
+            for k in 0..self.digits.len() {
+                let clause = squares.iter().map(|&square| self.var(square, k)).collect();
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    /// The digits of the two squares satisfy the relation, written as one clause per digit of the first that
+    /// rules out some digit of the second
+    fn relate(&mut self, first: (usize, usize), second: (usize, usize), relation: impl Fn(u64, u64) -> bool) {
+        for (k, &a) in self.digits.iter().enumerate() {
+            let allowed = self.digits.iter().enumerate().filter(|&(_, &b)| relation(a, b)).map(|(l, _)| self.var(second, l)).collect::<Vec<_>>();
+            if allowed.len() < self.digits.len() {
+                self.clauses.push([vec![-self.var(first, k)], allowed].concat());
+            }
+        }
+    }
+
+    fn decode(&self, values: &[i8]) -> Vec<Vec<u64>> {
+        (0..self.size).map(|i| (0..self.size).map(|j| {
+            let k = (0..self.digits.len()).find(|&k| values[self.var((i, j), k) as usize - 1] > 0).unwrap();
+            self.digits[k]
+        }).collect()).collect()
+    }
+}
+
+/// Rules of the sudoku that the one-hot encoding has no clauses for, by their JSON names
+pub fn unsupported_rules(sudoku: &Sudoku) -> Vec<&'static str> {
+    let whispers = !sudoku.german_whispers.is_empty() && matches!(sudoku.whisper_difference, Clue::Unknown(_));
+    [
+        (!sudoku.unknown_givens.is_empty(), "unknownGivens"),
+        (!sudoku.quadruples.is_empty(), "quadruples"),
+        (!sudoku.arrow.is_empty(), "arrow"),
+        (sudoku.consecutive_bars.is_some(), "consecutiveBars"),
+        (!sudoku.renban.is_empty(), "renban"),
+        (!sudoku.lockout_lines.is_empty(), "lockoutLines"),
+        (!sudoku.entropic_lines.is_empty(), "entropicLines"),
+        (!sudoku.modular_lines.is_empty(), "modularLines"),
+        (!sudoku.region_sum_lines.is_empty(), "regionSumLines"),
+        (!sudoku.row_indexing.is_empty() || !sudoku.column_indexing.is_empty(), "indexing"),
+        (whispers, "whisperDifference"),
+        (!sudoku.sandwich.is_empty(), "sandwich"),
+        (!sudoku.frame.is_empty(), "frame"),
+        (!sudoku.skyscraper.is_empty(), "skyscraper"),
+        (!sudoku.x_sum.is_empty(), "xSum"),
+        (!sudoku.unknowns.is_empty(), "unknowns"),
+        (!sudoku.masks.is_empty(), "masks"),
+        (!sudoku.killer_cages.is_empty(), "killerCages"),
+        (!sudoku.line_order.is_empty(), "lineOrder"),
+        (!sudoku.digit_counts.is_empty(), "digitCounts"),
+    ].into_iter().filter(|(used, _)| *used).map(|(_, name)| name).collect()
+}
+
+/// Clauses for the givens, the state, the classic rules and the variants that only relate pairs of squares,
+/// or the rules that need arithmetic and have no clauses
+pub fn encode(sudoku: &Sudoku) -> Result<Cnf, Vec<&'static str>> {
+    let unsupported = unsupported_rules(sudoku);
+    if !unsupported.is_empty() {
+        return Err(unsupported);
+    }
+    let size = sudoku.size();
+    let mut cnf = Cnf::new(size, &sudoku.digits);
+    for (i, row) in sudoku.given.iter().enumerate() {
+        for (j, given) in row.iter().enumerate() {
+            if let Some(digit) = given.or(sudoku.state.filled.get(i).and_then(|row| row[j])) {
+                cnf.allow((i, j), &[digit]);
+            }
+        }
+    }
+    for mark in &sudoku.state.pencilmarks {
+        cnf.allow((mark.cell[0], mark.cell[1]), &mark.digits);
+    }
+    for region in &sudoku.digit_regions {
+        for cell in &region.cells {
+            cnf.allow((cell[0], cell[1]), &region.digits);
+        }
+    }
+    let (odd, even): (Vec<u64>, Vec<u64>) = sudoku.digits.iter().partition(|&&digit| digit % 2 == 1);
+    for (cells, digits) in [(&sudoku.odd_cells, &odd), (&sudoku.even_cells, &even)] {
+        for cell in cells {
+            cnf.allow((cell[0], cell[1]), digits);
+        }
+    }
+    if !sudoku.odd_shading.is_empty() {
+        for i in 0..size {
+            for j in 0..size {
+                let shaded = sudoku.odd_shading.iter().any(|cell| cell[0] == i && cell[1] == j);
+                cnf.allow((i, j), if shaded { &odd } else { &even });
+            }
+        }
+    }
+
+    let mut regions = Vec::new();
+    if sudoku.horizontal_rule {
+        regions.extend((0..size).map(|i| (0..size).map(|j| (i, j)).collect::<Vec<_>>()));
+    }
+    if sudoku.vertical_rule {
+        regions.extend((0..size).map(|j| (0..size).map(|i| (i, j)).collect::<Vec<_>>()));
+    }
+    if sudoku.nonet_rule {
+        regions.extend(nonets(size));
+    }
+    if sudoku.windoku {
+        regions.extend(windows(size));
+    }
+    if sudoku.disjoint_groups {
+        regions.extend(disjoint_groups(size));
+    }
+    if sudoku.positive_diagonal {
+        regions.push((0..size).map(|k| (size - 1 - k, k)).collect());
+    }
+    if sudoku.negative_diagonal {
+        regions.push((0..size).map(|k| (k, k)).collect());
+    }
+    for region in &regions {
+        cnf.distinct(region);
+    }
+
+    let orthogonal = ORTHOGONAL.iter().map(|offset| offset.to_vec()).collect::<Vec<_>>();
+    let mut offsets = Vec::new();
+    if !sudoku.offset.is_empty() {
+        offsets.push((&sudoku.offset, OffsetRelation::NotEqual));
+    }
+    for group in &sudoku.offset_groups {
+        offsets.push((&group.offsets, group.relation.clone()));
+    }
+    if sudoku.non_consecutive {
+        offsets.push((&orthogonal, OffsetRelation::NotConsecutive));
+    }
+    for (offsets, relation) in offsets {
+        for i in 0..size {
+            for j in 0..size {
+                for offset in offsets {
+                    let (row, col) = (i as i32 + offset[0], j as i32 + offset[1]);
+                    if row < 0 || col < 0 || row >= size as i32 || col >= size as i32 {
+                        continue;
+                    }
+                    let other = (row as usize, col as usize);
+                    match relation {
+                        OffsetRelation::NotEqual => cnf.relate((i, j), other, |a, b| a != b),
+                        OffsetRelation::NotConsecutive => cnf.relate((i, j), other, |a, b| a.abs_diff(b) != 1),
+                        OffsetRelation::AtLeast(diff) => cnf.relate((i, j), other, |a, b| a.abs_diff(b) >= diff),
+                    }
+                }
+            }
+        }
+    }
+    if sudoku.taxicab {
+        for a in 0..size * size {
+            for b in (a + 1)..size * size {
+                let (first, second) = ((a / size, a % size), (b / size, b % size));
+                let distance = (first.0.abs_diff(second.0) + first.1.abs_diff(second.1)) as u64;
+                cnf.relate(first, second, |x, y| x != distance || y != distance);
+            }
+        }
+    }
+
+    let pairs = |line: &Vec<Vec<usize>>| line.windows(2).map(|pair| ((pair[0][0], pair[0][1]), (pair[1][0], pair[1][1]))).collect::<Vec<_>>();
+    for squares in &sudoku.thermo {
+        for (first, second) in pairs(squares) {
+            cnf.relate(first, second, |a, b| a < b);
+        }
+    }
+    for kropki in &sudoku.kropki_adjacent {
+        for (first, second) in pairs(kropki) {
+            cnf.relate(first, second, |a, b| a.abs_diff(b) == 1);
+        }
+    }
+    for kropki in &sudoku.kropki_double {
+        for (first, second) in pairs(kropki) {
+            cnf.relate(first, second, |a, b| a == 2 * b || b == 2 * a);
+        }
+    }
+    for kropki in &sudoku.kropki_ambiguous {
+        for (first, second) in pairs(kropki) {
+            cnf.relate(first, second, |a, b| a.abs_diff(b) == 1 || a == 2 * b || b == 2 * a);
+        }
+    }
+    if let Clue::Known(difference) = sudoku.whisper_difference {
+        for whisper in &sudoku.german_whispers {
+            for (first, second) in pairs(whisper) {
+                cnf.relate(first, second, |a, b| a.abs_diff(b) >= difference);
+            }
+        }
+    }
+
+    if sudoku.prune {
+        let mut candidates = Candidates::new(sudoku);
+        candidates.solve();
+        for (i, row) in candidates.grid.iter().enumerate() {
+            for (j, digits) in row.iter().enumerate() {
+                cnf.allow((i, j), digits);
+            }
+        }
+    }
+    Ok(cnf)
+}
+
+/// A DPLL search with unit propagation, enumerating satisfying assignments
+struct Dpll<'a> {
+    clauses: &'a [Vec<i32>],
+    /// The clauses each literal appears in, indexed by literal_index
+    occurrences: Vec<Vec<usize>>,
+    /// The clauses without a negated literal, a square's possible digits or a digit's possible squares, which
+    /// are all satisfied once every square has a digit
+    positive: Vec<usize>,
+    /// 1 for true, -1 for false and 0 for unassigned, one per variable
+    values: Vec<i8>,
+    trail: Vec<i32>,
+}
+
+fn literal_index(literal: i32) -> usize {
+    2 * (literal.unsigned_abs() as usize - 1) + (literal < 0) as usize
+}
+
+impl Dpll<'_> {
+    fn value(&self, literal: i32) -> i8 {
+        let value = self.values[literal.unsigned_abs() as usize - 1];
+        if literal > 0 { value } else { -value }
+    }
+
+    fn assign(&mut self, literal: i32) {
+        self.values[literal.unsigned_abs() as usize - 1] = if literal > 0 { 1 } else { -1 };
+        self.trail.push(literal);
+    }
+
+    fn undo(&mut self, length: usize) {
+        while self.trail.len() > length {
+            let literal = self.trail.pop().unwrap();
+            self.values[literal.unsigned_abs() as usize - 1] = 0;
+        }
+    }
+
+    /// Assigns every literal forced by the assignments on the trail from start on, false on a conflict
+    fn propagate(&mut self, start: usize) -> bool {
+        let mut head = start;
+        while head < self.trail.len() {
+            let falsified = literal_index(-self.trail[head]);
+            head += 1;
+            for n in 0..self.occurrences[falsified].len() {
+                let clause = &self.clauses[self.occurrences[falsified][n]];
+                let mut open = None;
+                let mut count = 0;
+                if clause.iter().any(|&literal| self.value(literal) > 0) {
+                    continue;
+                }
+                for &literal in clause {
+                    if self.value(literal) == 0 {
+                        open = Some(literal);
+                        count += 1;
+                    }
+                }
+                match (count, open) {
+                    (0, _) => return false,
+                    (1, Some(literal)) => self.assign(literal),
+                    _ => {},
+                }
+            }
+        }
+        true
+    }
+
+    /// Branches on a literal of the open positive clause with the fewest choices left, trying it and then its
+    /// negation, and records solutions until there are limit of them
+    fn search(&mut self, limit: usize, solutions: &mut Vec<Vec<i8>>) {
+        let clauses = self.clauses;
+        let open = self.positive.iter().map(|&c| &clauses[c]).filter(|clause| clause.iter().all(|&literal| self.value(literal) <= 0)).min_by_key(
+            |clause| clause.iter().filter(|&&literal| self.value(literal) == 0).count()
+        );
+        let Some(clause) = open else {
+            solutions.push(self.values.clone());
+            return;
+        };
+        let Some(&literal) = clause.iter().find(|&&literal| self.value(literal) == 0) else {
+            return;
+        };
+        for choice in [literal, -literal] {
+            if solutions.len() >= limit {
+                return;
+            }
+            let length = self.trail.len();
+            self.assign(choice);
+            if self.propagate(length) {
+                self.search(limit, solutions);
+            }
+            self.undo(length);
+        }
+    }
+}
+
+impl Cnf {
+    /// Up to limit distinct solutions, as grids of digits
+    pub fn solutions(&self, limit: usize) -> Vec<Vec<Vec<u64>>> {
+        let mut dpll = Dpll {
+            clauses: &self.clauses,
+            occurrences: vec![Vec::new(); 2 * self.variables()],
+            positive: (0..self.clauses.len()).filter(|&c| self.clauses[c].iter().all(|&literal| literal > 0)).collect(),
+            values: vec![0; self.variables()],
+            trail: Vec::new(),
+        };
+        for (c, clause) in self.clauses.iter().enumerate() {
+            for &literal in clause {
+                dpll.occurrences[literal_index(literal)].push(c);
+            }
+        }
+        for clause in &self.clauses {
+            match clause[..] {
+                [] => return Vec::new(),
+                [literal] if dpll.value(literal) < 0 => return Vec::new(),
+                [literal] if dpll.value(literal) == 0 => dpll.assign(literal),
+                _ => {},
+            }
+        }
+        let mut solutions = Vec::new();
+        if dpll.propagate(0) {
+            dpll.search(limit, &mut solutions);
+        }
+        solutions.iter().map(|values| self.decode(values)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_classic() {
+        let sudoku = crate::classic_sudoku(&[
+            "53..7....", "6..195...", ".98....6.", "8...6...3", "4..8.3..1", "7...2...6", ".6....28.", "...419..5", "....8..79",
+        ]);
+        let solutions = encode(&sudoku).unwrap().solutions(2);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][0], [5, 3, 4, 6, 7, 8, 9, 1, 2]);
+        assert_eq!(solutions[0][8], [3, 4, 5, 2, 8, 6, 1, 7, 9]);
+    }
+
+    #[test]
+    fn counts_up_to_the_limit() {
+        let sudoku = crate::classic_sudoku(&[
+            ".........", "6..195...", ".98....6.", "8...6...3", "4..8.3..1", "7...2...6", ".6....28.", "...419..5", "....8..79",
+        ]);
+        let solutions = encode(&sudoku).unwrap().solutions(5);
+        assert!(solutions.len() > 1);
+        for (p, first) in solutions.iter().enumerate() {
+            assert!(solutions[p + 1..].iter().all(|second| second != first));
+        }
+    }
+
+    #[test]
+    fn rejects_arithmetic_rules() {
+        let mut sudoku = crate::classic_sudoku(&["........."; 9]);
+        sudoku.killer_cages.push(crate::Cage { cells: vec![vec![0, 0], vec![0, 1]], sum: None, relation: crate::CageRelation::Equal });
+        assert_eq!(encode(&sudoku).err(), Some(vec!["killerCages"]));
+    }
+}
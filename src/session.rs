@@ -0,0 +1,72 @@
+use z3::{Context, SatResult, Solver};
+use z3::ast::{Ast, Int};
+
+use crate::{count_up_to, new_grid, solution_from_model, Sudoku};
+use crate::solver::add_solver_constraints;
+
+/// A sudoku loaded into a solver once so it can be queried repeatedly. The context is owned by the caller and
+/// only borrowed, so any number of sessions can be created and dropped while it lives.
+///
+/// ```
+/// use sudoku_z3::Session;
+/// use sudoku_z3::fixtures::FIXTURES;
+///
+/// let sudoku = FIXTURES[0].sudoku();
+/// let config = z3::Config::new();
+/// let ctx = z3::Context::new(&config);
+/// let session = Session::new(&sudoku, &ctx);
+/// assert_eq!(session.count(10), 4);
+/// let solution = session.solve().unwrap();
+/// assert_eq!(solution[2], [3, 7, 5, 6, 1, 8, 2, 4, 9]);
+/// let fixed = session.with_fixed(&[((0, 0), 9)], |session| session.count(10));
+/// assert_eq!(fixed, 2);
+/// ```
+pub struct Session<'ctx> {
+    pub ctx: &'ctx Context,
+    pub grid: Vec<Vec<Int<'ctx>>>,
+    pub solver: Solver<'ctx>,
+    digits: Vec<u64>,
+}
+
+impl<'ctx> Session<'ctx> {
+    pub fn new(sudoku: &Sudoku, ctx: &'ctx Context) -> Session<'ctx> {
+        let grid = new_grid(ctx, sudoku.size());
+        let solver = Solver::new(ctx);
+        add_solver_constraints(sudoku, &grid, &solver, ctx);
+        Session { ctx, grid, solver, digits: sudoku.digits.clone() }
+    }
+
+    /// Any solution, or None if there is none
+    pub fn solve(&self) -> Option<Vec<Vec<u64>>> {
+        match self.solver.check() {
+            SatResult::Sat => Some(solution_from_model(&self.solver.get_model().unwrap(), &self.grid)),
+            SatResult::Unsat => None,
+            SatResult::Unknown => panic!("Solver returned unknown!"),
+        }
+    }
+
+    /// Number of solutions, counting no further than the limit
+    pub fn count(&self, limit: u32) -> u32 {
+        count_up_to(&self.grid, &self.solver, self.ctx, limit)
+    }
+
+    /// Every digit each square can hold in some solution
+    pub fn candidates(&self) -> Vec<Vec<Vec<u64>>> {
+        (0..self.grid.len()).map(|i| (0..self.grid.len()).map(|j| {
+            self.digits.iter().copied().filter(
+                |&digit| self.with_fixed(&[((i, j), digit)], |session| session.solver.check() == SatResult::Sat)
+            ).collect()
+        }).collect()).collect()
+    }
+
+    /// Runs a query with some squares fixed to digits, leaving the session as it was afterwards
+    pub fn with_fixed<T>(&self, fixed: &[((usize, usize), u64)], query: impl FnOnce(&Self) -> T) -> T {
+        self.solver.push();
+        for &((i, j), digit) in fixed {
+            self.solver.assert(&self.grid[i][j]._eq(&Int::from_u64(self.ctx, digit)));
+        }
+        let result = query(self);
+        self.solver.pop(1);
+        result
+    }
+}
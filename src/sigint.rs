@@ -0,0 +1,21 @@
+use std::thread::Scope;
+
+use z3::Context;
+
+use sudoku_z3::interrupt::{interrupt, CancelToken};
+
+/// Exit code after Ctrl-C, 128 plus the signal number as shells report it
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    interrupt();
+}
+
+/// A token cancelling the queries of the context that Ctrl-C also triggers. The signal handler only sets a
+/// flag and the token's watcher passes it on, since Z3 takes locks to interrupt.
+pub fn interruptible<'ctx: 'scope, 'scope>(ctx: &'ctx Context, scope: &'scope Scope<'scope, '_>) -> CancelToken<'ctx> {
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+    CancelToken::new(ctx, scope)
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Value};
+
+use crate::{Clue, Sudoku};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A SudokuPad link to the puzzle with progress filled in: the givens, the digits of the state and every
+/// digit forced by the candidates as entered digits, and the candidates of every other square narrower than
+/// the full digit set as centre pencilmarks. The candidates come from Session::candidates, which already
+/// respects the state, so the pencilmarks of the state are carried through them. Killer cages and
+/// thermometers are drawn; other rules are left to the puzzle text.
+pub fn progress_url(sudoku: &Sudoku, candidates: &[Vec<Vec<u64>>]) -> String {
+    format!("https://sudokupad.app/fpuzzles{}", compress_to_base64(&fpuzzles(sudoku, candidates).to_string()))
+}
+
+/// The puzzle and its progress in the f-puzzles JSON format, which SudokuPad imports
+pub fn fpuzzles(sudoku: &Sudoku, candidates: &[Vec<Vec<u64>>]) -> Value {
+    let size = sudoku.size();
+    let grid = (0..size).map(|i| (0..size).map(|j| {
+        if let Some(digit) = sudoku.given[i][j] {
+            json!({ "value": digit, "given": true })
+        } else if let Some(digit) = sudoku.state.filled.get(i).and_then(|row| row[j]) {
+            json!({ "value": digit })
+        } else if let [digit] = candidates[i][j][..] {
+            json!({ "value": digit })
+        } else if candidates[i][j].len() < sudoku.digits.len() {
+            json!({ "centerPencilMarks": candidates[i][j] })
+        } else {
+            json!({})
+        }
+    }).collect::<Vec<_>>()).collect::<Vec<_>>();
+    let mut value = json!({ "size": size, "grid": grid });
+    if !sudoku.killer_cages.is_empty() {
+        value["killercage"] = sudoku.killer_cages.iter().map(|cage| {
+            let mut killer = json!({ "cells": cage.cells.iter().map(|cell| cell_name(cell)).collect::<Vec<_>>() });
+            match &cage.sum {
+                Some(Clue::Known(sum)) => killer["value"] = json!(sum.to_string()),
+                Some(Clue::Unknown(_)) => killer["value"] = json!("?"),
+                None => {},
+            }
+            killer
+        }).collect();
+    }
+    if !sudoku.thermo.is_empty() {
+        value["thermometer"] = sudoku.thermo.iter().map(|squares| json!({
+            "lines": [squares.iter().map(|cell| cell_name(cell)).collect::<Vec<_>>()],
+        })).collect();
+    }
+    value
+}
+
+/// A square in f-puzzles notation, counting rows and columns from 1
+fn cell_name(cell: &[usize]) -> String {
+    format!("R{}C{}", cell[0] + 1, cell[1] + 1)
+}
+
+/// The compressToBase64 function of lz-string, which SudokuPad uses to shorten puzzle links
+pub fn compress_to_base64(input: &str) -> String {
+    let mut output = compress(&input.encode_utf16().collect::<Vec<_>>(), 6, |value| BASE64[value as usize] as char);
+    while output.len() % 4 != 0 {
+        output.push('=');
+    }
+    output
+}
+
+/// Writes values a few bits at a time, lowest bit first, packing them into output characters
+struct BitWriter<F: Fn(u32) -> char> {
+    bits_per_char: u32,
+    to_char: F,
+    value: u32,
+    position: u32,
+    output: String,
+}
+
+impl<F: Fn(u32) -> char> BitWriter<F> {
+    fn write_bit(&mut self, bit: u32) {
+        self.value = (self.value << 1) | bit;
+        if self.position == self.bits_per_char - 1 {
+            self.position = 0;
+            self.output.push((self.to_char)(self.value));
+            self.value = 0;
+        } else {
+            self.position += 1;
+        }
+    }
+
+    fn write(&mut self, mut value: u32, bits: u32) {
+        for _ in 0..bits {
+            self.write_bit(value & 1);
+            value >>= 1;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        loop {
+            self.value <<= 1;
+            if self.position == self.bits_per_char - 1 {
+                self.output.push((self.to_char)(self.value));
+                return self.output;
+            }
+            self.position += 1;
+        }
+    }
+}
+
+/// The LZW-style core of lz-string over UTF-16 code units, emitting bits_per_char bits per output character
+fn compress(input: &[u16], bits_per_char: u32, to_char: impl Fn(u32) -> char) -> String {
+    let mut dictionary: HashMap<Vec<u16>, u32> = HashMap::new();
+    let mut to_create: HashSet<Vec<u16>> = HashSet::new();
+    let mut writer = BitWriter { bits_per_char, to_char, value: 0, position: 0, output: String::new() };
+    let (mut enlarge_in, mut dictionary_size, mut bits) = (2u32, 3u32, 2u32);
+    let mut w: Vec<u16> = Vec::new();
+
+    // Emits the phrase w, introducing it as a literal the first time it is seen
+    let mut emit = |w: &[u16], dictionary: &HashMap<Vec<u16>, u32>, to_create: &mut HashSet<Vec<u16>>, enlarge_in: &mut u32, bits: &mut u32| {
+        if to_create.remove(w) {
+            if w[0] < 256 {
+                writer.write(0, *bits);
+                writer.write(w[0] as u32, 8);
+            } else {
+                writer.write(1, *bits);
+                writer.write(w[0] as u32, 16);
+            }
+            *enlarge_in -= 1;
+            if *enlarge_in == 0 {
+                *enlarge_in = 1 << *bits;
+                *bits += 1;
+            }
+        } else {
+            writer.write(dictionary[w], *bits);
+        }
+        *enlarge_in -= 1;
+        if *enlarge_in == 0 {
+            *enlarge_in = 1 << *bits;
+            *bits += 1;
+        }
+    };
+
+    for &c in input {
+        if !dictionary.contains_key(&[c][..]) {
+            dictionary.insert(vec![c], dictionary_size);
+            dictionary_size += 1;
+            to_create.insert(vec![c]);
+        }
+        let mut wc = w.clone();
+        wc.push(c);
+        if dictionary.contains_key(&wc) {
+            w = wc;
+        } else {
+            emit(&w, &dictionary, &mut to_create, &mut enlarge_in, &mut bits);
+            dictionary.insert(wc, dictionary_size);
+            dictionary_size += 1;
+            w = vec![c];
+        }
+    }
+    if !w.is_empty() {
+        emit(&w, &dictionary, &mut to_create, &mut enlarge_in, &mut bits);
+    }
+    // The end of stream marker
+    writer.write(2, bits);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compresses_like_lz_string() {
+        assert_eq!(compress_to_base64("Hello, world"), "BIUwNmD2A0AEDukBOYAmQ===");
+        assert_eq!(
+            compress_to_base64("{\"grid\":[[{\"value\":1},{\"value\":1},{\"value\":12}]],\"title\":\"ア日本\"}"),
+            "N4Ig5gTglgJiBcBtRoBuBDANgVwKYIEYBfAGjSz0NPJ33gICYiBdZkkAFyg8zpECKGQKemgGnMQRIA==",
+        );
+    }
+
+    #[test]
+    fn givens_state_and_candidates() {
+        let mut sudoku = crate::classic_sudoku(&["5........", ".........", ".........", ".........", ".........", ".........", ".........", ".........", "........."]);
+        sudoku.state.filled[0][1] = Some(3);
+        let mut candidates = vec![vec![(1..=9).collect::<Vec<u64>>(); 9]; 9];
+        candidates[0][2] = vec![7];
+        candidates[0][3] = vec![1, 2];
+        let grid = &fpuzzles(&sudoku, &candidates)["grid"][0];
+        assert_eq!(grid[0], json!({ "value": 5, "given": true }));
+        assert_eq!(grid[1], json!({ "value": 3 }));
+        assert_eq!(grid[2], json!({ "value": 7 }));
+        assert_eq!(grid[3], json!({ "centerPencilMarks": [1, 2] }));
+        assert_eq!(grid[4], json!({}));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::Sudoku;
+use crate::logic::Candidates;
+use crate::session::Session;
+
+/// The square a solver should fill next and why it is safe
+pub struct NextDigit {
+    pub cell: (usize, usize),
+    pub digit: u64,
+    pub reason: String,
+}
+
+/// The easiest square to fill from the givens and the state. Human techniques are tried first, easiest
+/// first, and when they get stuck the square with the fewest candidates found by the solver is taken if
+/// that is a single digit. None if every square is filled or no square is forced.
+pub fn next_safe_digit(sudoku: &Sudoku, session: &Session) -> Option<NextDigit> {
+    let mut candidates = Candidates::new(sudoku);
+    let mut eliminations = 0;
+    while !candidates.contradiction() {
+        let Some(step) = candidates.next_step() else {
+            break;
+        };
+        if let Some((i, j, digit)) = step.placement {
+            let reason = match eliminations {
+                0 => step.technique.name().to_lowercase(),
+                1 => format!("{} after 1 elimination step", step.technique.name().to_lowercase()),
+                _ => format!("{} after {eliminations} elimination steps", step.technique.name().to_lowercase()),
+            };
+            return Some(NextDigit { cell: (i, j), digit, reason });
+        }
+        candidates.apply(&step);
+        eliminations += 1;
+    }
+
+    let open = |i: usize, j: usize| sudoku.given[i][j].is_none() && sudoku.state.filled[i][j].is_none();
+    let (cell, digits) = session.candidates().into_iter().enumerate()
+        .flat_map(|(i, row)| row.into_iter().enumerate().map(move |(j, digits)| ((i, j), digits)))
+        .filter(|&((i, j), _)| open(i, j))
+        .min_by_key(|(_, digits)| digits.len())?;
+    match digits[..] {
+        [digit] => Some(NextDigit { cell, digit, reason: "the only digit left by every constraint".to_string() }),
+        _ => None,
+    }
+}